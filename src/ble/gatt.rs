@@ -0,0 +1,495 @@
+//! A GATT server over a static attribute database.
+//!
+//! [`GattServer`] implements the Attribute Protocol (ATT) requests a central
+//! sends right after connecting: Exchange MTU, Find Information (descriptor
+//! discovery), Read By Group Type (service discovery), Read By Type
+//! (characteristic discovery), Read and Write. It answers out of a
+//! `&'static [`[`Attribute`]`]` table, so a whole GATT database (services,
+//! characteristics, their values) can be declared as plain `const`/`static`
+//! data with no heap allocation; [`BatteryServiceAttrs`] is a worked example
+//! other services (e.g. a Device Information Service) can be declared the
+//! same way.
+//!
+//! [`GattServer`] implements [`ChannelMapper`] and is meant to be handed
+//! straight to a [`Responder`] for the fixed ATT channel.
+//!
+//! [`Responder`]: ::responder::Responder
+
+use byteorder::{ByteOrder, LittleEndian};
+
+use ble::l2cap::{ChannelMapper, ATT_CID, MAX_SDU_SIZE};
+
+/// UUID of the *Primary Service* declaration attribute type.
+pub const PRIMARY_SERVICE_UUID: u16 = 0x2800;
+/// UUID of the *Characteristic* declaration attribute type.
+pub const CHARACTERISTIC_UUID: u16 = 0x2803;
+/// UUID of the Battery Service.
+pub const BATTERY_SERVICE_UUID: u16 = 0x180F;
+/// UUID of the Battery Level characteristic.
+pub const BATTERY_LEVEL_UUID: u16 = 0x2A19;
+
+/// The ATT MTU in effect before an Exchange MTU procedure completes.
+const DEFAULT_MTU: u16 = 23;
+
+/// ATT opcodes this server understands, or sends back in responses.
+mod opcode {
+    pub const ERROR_RESPONSE: u8 = 0x01;
+    pub const EXCHANGE_MTU_REQUEST: u8 = 0x02;
+    pub const EXCHANGE_MTU_RESPONSE: u8 = 0x03;
+    pub const FIND_INFORMATION_REQUEST: u8 = 0x04;
+    pub const FIND_INFORMATION_RESPONSE: u8 = 0x05;
+    pub const READ_BY_TYPE_REQUEST: u8 = 0x08;
+    pub const READ_BY_TYPE_RESPONSE: u8 = 0x09;
+    pub const READ_REQUEST: u8 = 0x0A;
+    pub const READ_RESPONSE: u8 = 0x0B;
+    pub const READ_BY_GROUP_TYPE_REQUEST: u8 = 0x10;
+    pub const READ_BY_GROUP_TYPE_RESPONSE: u8 = 0x11;
+    pub const WRITE_REQUEST: u8 = 0x12;
+    pub const WRITE_RESPONSE: u8 = 0x13;
+}
+
+/// ATT error codes, sent back in an Error Response.
+#[derive(Copy, Clone, Debug)]
+pub enum AttError {
+    /// The handle in the request doesn't exist in the attribute table.
+    InvalidHandle = 0x01,
+    /// The attribute exists but cannot be read.
+    ReadNotPermitted = 0x02,
+    /// The attribute exists but cannot be written (the database is static).
+    WriteNotPermitted = 0x03,
+    /// The request PDU was malformed.
+    InvalidPdu = 0x04,
+    /// The opcode isn't one this server implements.
+    RequestNotSupported = 0x06,
+    /// No attribute matching the request criteria was found in range.
+    AttributeNotFound = 0x0A,
+}
+
+/// A Bluetooth attribute UUID: either the 16-bit Bluetooth SIG-assigned form
+/// or a full 128-bit UUID.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Uuid {
+    /// A 16-bit UUID assigned by the Bluetooth SIG.
+    Uuid16(u16),
+    /// A full 128-bit UUID.
+    Uuid128([u8; 16]),
+}
+
+impl Uuid {
+    /// Returns the length of this UUID's little-endian wire encoding.
+    fn len(&self) -> usize {
+        match *self {
+            Uuid::Uuid16(_) => 2,
+            Uuid::Uuid128(_) => 16,
+        }
+    }
+
+    /// Encodes this UUID in little-endian wire format into `out`.
+    fn encode(&self, out: &mut [u8]) {
+        match *self {
+            Uuid::Uuid16(uuid) => LittleEndian::write_u16(out, uuid),
+            Uuid::Uuid128(bytes) => out[..16].copy_from_slice(&bytes),
+        }
+    }
+
+    /// Returns `true` if `bytes`, a little-endian wire-format UUID, denotes
+    /// this UUID.
+    fn matches(&self, bytes: &[u8]) -> bool {
+        match *self {
+            Uuid::Uuid16(uuid) => bytes.len() == 2 && LittleEndian::read_u16(bytes) == uuid,
+            Uuid::Uuid128(expected) => bytes.len() == 16 && bytes == &expected[..],
+        }
+    }
+}
+
+/// A single entry of a GATT attribute table.
+///
+/// Service, characteristic and descriptor declarations, as well as
+/// characteristic values, are all just attributes distinguished by `uuid`.
+#[derive(Copy, Clone)]
+pub struct Attribute {
+    /// The 1-based handle identifying this attribute within the table.
+    pub handle: u16,
+    /// The attribute type (e.g. [`PRIMARY_SERVICE_UUID`]) or, for a
+    /// characteristic value, the characteristic's own UUID.
+    pub uuid: Uuid,
+    /// The attribute's value.
+    pub value: &'static [u8],
+}
+
+/// An ATT server answering requests out of a static attribute table.
+///
+/// `attrs` must be sorted in ascending order of [`Attribute::handle`], as
+/// required by the Attribute Protocol.
+pub struct GattServer<'a> {
+    attrs: &'a [Attribute],
+    mtu: u16,
+}
+
+impl<'a> GattServer<'a> {
+    /// Creates a server answering out of `attrs`.
+    pub fn new(attrs: &'a [Attribute]) -> Self {
+        debug_assert!(
+            attrs.windows(2).all(|w| w[0].handle < w[1].handle),
+            "attribute table must be sorted by ascending handle"
+        );
+        GattServer { attrs, mtu: DEFAULT_MTU }
+    }
+
+    /// Dispatches a single ATT request PDU, writing the response into `out`.
+    fn handle_att_pdu(&mut self, pdu: &[u8], out: &mut [u8; MAX_SDU_SIZE]) -> usize {
+        match pdu[0] {
+            opcode::EXCHANGE_MTU_REQUEST => self.exchange_mtu(pdu, out),
+            opcode::FIND_INFORMATION_REQUEST => self.find_information(pdu, out),
+            opcode::READ_BY_GROUP_TYPE_REQUEST => self.read_by_group_type(pdu, out),
+            opcode::READ_BY_TYPE_REQUEST => self.read_by_type(pdu, out),
+            opcode::READ_REQUEST => self.read(pdu, out),
+            opcode::WRITE_REQUEST => self.write(pdu, out),
+            other => error_response(out, other, 0, AttError::RequestNotSupported),
+        }
+    }
+
+    /// Handles an Exchange MTU Request (0x02).
+    fn exchange_mtu(&mut self, pdu: &[u8], out: &mut [u8; MAX_SDU_SIZE]) -> usize {
+        if pdu.len() < 3 {
+            return error_response(out, opcode::EXCHANGE_MTU_REQUEST, 0, AttError::InvalidPdu);
+        }
+
+        let client_mtu = LittleEndian::read_u16(&pdu[1..3]);
+        self.mtu = client_mtu.max(DEFAULT_MTU).min(MAX_SDU_SIZE as u16);
+
+        out[0] = opcode::EXCHANGE_MTU_RESPONSE;
+        LittleEndian::write_u16(&mut out[1..3], self.mtu);
+        3
+    }
+
+    /// Handles a Find Information Request (0x04): descriptor discovery.
+    fn find_information(&mut self, pdu: &[u8], out: &mut [u8; MAX_SDU_SIZE]) -> usize {
+        if pdu.len() < 5 {
+            return error_response(out, opcode::FIND_INFORMATION_REQUEST, 0, AttError::InvalidPdu);
+        }
+        let start = LittleEndian::read_u16(&pdu[1..3]);
+        let end = LittleEndian::read_u16(&pdu[3..5]);
+        if start == 0 || start > end {
+            return error_response(out, opcode::FIND_INFORMATION_REQUEST, start, AttError::InvalidHandle);
+        }
+
+        // `format` is 0 until the first match fixes whether this response
+        // carries 16-bit or 128-bit UUIDs; a later entry of the other width
+        // ends the response instead of mixing formats.
+        let mut format = 0u8;
+        let mut pos = 2;
+        for attr in self.attrs.iter().filter(|a| a.handle >= start && a.handle <= end) {
+            let this_format = if attr.uuid.len() == 2 { 1 } else { 2 };
+            if format == 0 {
+                format = this_format;
+            } else if this_format != format {
+                break;
+            }
+
+            let entry_size = 2 + attr.uuid.len();
+            if pos + entry_size > self.mtu as usize || pos + entry_size > MAX_SDU_SIZE {
+                break;
+            }
+            LittleEndian::write_u16(&mut out[pos..pos + 2], attr.handle);
+            attr.uuid.encode(&mut out[pos + 2..pos + entry_size]);
+            pos += entry_size;
+        }
+
+        if format == 0 {
+            return error_response(out, opcode::FIND_INFORMATION_REQUEST, start, AttError::AttributeNotFound);
+        }
+        out[0] = opcode::FIND_INFORMATION_RESPONSE;
+        out[1] = format;
+        pos
+    }
+
+    /// Handles a Read By Group Type Request (0x10): service discovery.
+    fn read_by_group_type(&mut self, pdu: &[u8], out: &mut [u8; MAX_SDU_SIZE]) -> usize {
+        if pdu.len() < 7 {
+            return error_response(out, opcode::READ_BY_GROUP_TYPE_REQUEST, 0, AttError::InvalidPdu);
+        }
+        let start = LittleEndian::read_u16(&pdu[1..3]);
+        let end = LittleEndian::read_u16(&pdu[3..5]);
+        let group_type = &pdu[5..];
+        if start == 0 || start > end {
+            return error_response(out, opcode::READ_BY_GROUP_TYPE_REQUEST, start, AttError::InvalidHandle);
+        }
+
+        let mut value_len = None;
+        let mut pos = 2;
+        let mut i = 0;
+        while i < self.attrs.len() {
+            let attr = &self.attrs[i];
+            if attr.handle > end {
+                break;
+            }
+            if attr.handle < start || !attr.uuid.matches(group_type) {
+                i += 1;
+                continue;
+            }
+
+            let len = *value_len.get_or_insert_with(|| attr.value.len());
+            if attr.value.len() != len {
+                break;
+            }
+
+            // The group spans from this attribute up to, but not including,
+            // the next one of the same group type (or the end of the table).
+            let mut j = i + 1;
+            while j < self.attrs.len() && !self.attrs[j].uuid.matches(group_type) {
+                j += 1;
+            }
+            let group_end = if j < self.attrs.len() {
+                self.attrs[j].handle - 1
+            } else {
+                self.attrs[self.attrs.len() - 1].handle
+            };
+
+            let entry_size = 4 + len;
+            if pos + entry_size > self.mtu as usize || pos + entry_size > MAX_SDU_SIZE {
+                break;
+            }
+            LittleEndian::write_u16(&mut out[pos..pos + 2], attr.handle);
+            LittleEndian::write_u16(&mut out[pos + 2..pos + 4], group_end);
+            out[pos + 4..pos + entry_size].copy_from_slice(attr.value);
+            pos += entry_size;
+
+            i = j;
+        }
+
+        let value_len = match value_len {
+            Some(len) => len,
+            None => return error_response(out, opcode::READ_BY_GROUP_TYPE_REQUEST, start, AttError::AttributeNotFound),
+        };
+        out[0] = opcode::READ_BY_GROUP_TYPE_RESPONSE;
+        out[1] = (4 + value_len) as u8;
+        pos
+    }
+
+    /// Handles a Read By Type Request (0x08): characteristic discovery.
+    fn read_by_type(&mut self, pdu: &[u8], out: &mut [u8; MAX_SDU_SIZE]) -> usize {
+        if pdu.len() < 7 {
+            return error_response(out, opcode::READ_BY_TYPE_REQUEST, 0, AttError::InvalidPdu);
+        }
+        let start = LittleEndian::read_u16(&pdu[1..3]);
+        let end = LittleEndian::read_u16(&pdu[3..5]);
+        let attr_type = &pdu[5..];
+        if start == 0 || start > end {
+            return error_response(out, opcode::READ_BY_TYPE_REQUEST, start, AttError::InvalidHandle);
+        }
+
+        let mut value_len = None;
+        let mut pos = 2;
+        for attr in self.attrs.iter().filter(|a| a.handle >= start && a.handle <= end) {
+            if !attr.uuid.matches(attr_type) {
+                continue;
+            }
+
+            let len = *value_len.get_or_insert_with(|| attr.value.len());
+            if attr.value.len() != len {
+                break;
+            }
+
+            let entry_size = 2 + len;
+            if pos + entry_size > self.mtu as usize || pos + entry_size > MAX_SDU_SIZE {
+                break;
+            }
+            LittleEndian::write_u16(&mut out[pos..pos + 2], attr.handle);
+            out[pos + 2..pos + entry_size].copy_from_slice(attr.value);
+            pos += entry_size;
+        }
+
+        let value_len = match value_len {
+            Some(len) => len,
+            None => return error_response(out, opcode::READ_BY_TYPE_REQUEST, start, AttError::AttributeNotFound),
+        };
+        out[0] = opcode::READ_BY_TYPE_RESPONSE;
+        out[1] = (2 + value_len) as u8;
+        pos
+    }
+
+    /// Handles a Read Request (0x0A).
+    fn read(&mut self, pdu: &[u8], out: &mut [u8; MAX_SDU_SIZE]) -> usize {
+        if pdu.len() < 3 {
+            return error_response(out, opcode::READ_REQUEST, 0, AttError::InvalidPdu);
+        }
+        let handle = LittleEndian::read_u16(&pdu[1..3]);
+        let attr = match self.attrs.iter().find(|a| a.handle == handle) {
+            Some(attr) => attr,
+            None => return error_response(out, opcode::READ_REQUEST, handle, AttError::InvalidHandle),
+        };
+
+        out[0] = opcode::READ_RESPONSE;
+        let max_value = (self.mtu as usize - 1).min(MAX_SDU_SIZE - 1);
+        let n = attr.value.len().min(max_value);
+        out[1..1 + n].copy_from_slice(&attr.value[..n]);
+        1 + n
+    }
+
+    /// Handles a Write Request (0x12).
+    ///
+    /// The attribute table is static, so any write to a handle that exists is
+    /// rejected with [`WriteNotPermitted`](AttError::WriteNotPermitted).
+    fn write(&mut self, pdu: &[u8], out: &mut [u8; MAX_SDU_SIZE]) -> usize {
+        if pdu.len() < 3 {
+            return error_response(out, opcode::WRITE_REQUEST, 0, AttError::InvalidPdu);
+        }
+        let handle = LittleEndian::read_u16(&pdu[1..3]);
+        let error = if self.attrs.iter().any(|a| a.handle == handle) {
+            AttError::WriteNotPermitted
+        } else {
+            AttError::InvalidHandle
+        };
+        error_response(out, opcode::WRITE_REQUEST, handle, error)
+    }
+}
+
+impl<'a> ChannelMapper for GattServer<'a> {
+    fn handle(&mut self, cid: u16, sdu: &[u8], response: &mut [u8; MAX_SDU_SIZE]) -> Option<usize> {
+        // The LE signaling channel (connection parameter updates, etc.) isn't
+        // handled yet; only the fixed ATT channel is.
+        if cid != ATT_CID || sdu.is_empty() {
+            return None;
+        }
+        Some(self.handle_att_pdu(sdu, response))
+    }
+}
+
+/// Writes an ATT Error Response for `opcode`/`handle`/`error` into `out`.
+fn error_response(out: &mut [u8; MAX_SDU_SIZE], opcode: u8, handle: u16, error: AttError) -> usize {
+    out[0] = opcode::ERROR_RESPONSE;
+    out[1] = opcode;
+    LittleEndian::write_u16(&mut out[2..4], handle);
+    out[4] = error as u8;
+    5
+}
+
+/// The characteristic declaration value for [`BatteryServiceAttrs`]'s Battery
+/// Level characteristic: properties (Read only), the value handle, and the
+/// characteristic UUID.
+const BATTERY_LEVEL_CHAR_DECL: [u8; 5] = [
+    0x02, // properties: Read
+    (BatteryServiceAttrs::LEVEL_HANDLE & 0xFF) as u8,
+    (BatteryServiceAttrs::LEVEL_HANDLE >> 8) as u8,
+    (BATTERY_LEVEL_UUID & 0xFF) as u8,
+    (BATTERY_LEVEL_UUID >> 8) as u8,
+];
+
+/// The Battery Service UUID (0x180F), little-endian.
+const BATTERY_SERVICE_UUID_BYTES: [u8; 2] =
+    [(BATTERY_SERVICE_UUID & 0xFF) as u8, (BATTERY_SERVICE_UUID >> 8) as u8];
+
+/// A ready-made attribute table for the standard Battery Service (0x180F),
+/// exposing a single read-only Battery Level characteristic (0x2A19).
+///
+/// This is a worked example of the pattern [`GattServer`] expects: every
+/// attribute value lives in `'static` storage, so the whole service can be
+/// declared without heap allocation. A Device Information Service (or any
+/// other) is added the same way: more `const`/`static` [`Attribute`]s,
+/// continuing the handle numbering.
+pub struct BatteryServiceAttrs;
+
+impl BatteryServiceAttrs {
+    /// Handle of the service declaration.
+    pub const SERVICE_HANDLE: u16 = 1;
+    /// Handle of the characteristic declaration.
+    pub const CHAR_DECL_HANDLE: u16 = 2;
+    /// Handle of the Battery Level characteristic value.
+    pub const LEVEL_HANDLE: u16 = 3;
+
+    /// Builds the 3-attribute table, backed by `level`, the current battery
+    /// percentage (0..=100).
+    pub const fn attributes(level: &'static [u8; 1]) -> [Attribute; 3] {
+        [
+            Attribute {
+                handle: Self::SERVICE_HANDLE,
+                uuid: Uuid::Uuid16(PRIMARY_SERVICE_UUID),
+                value: &BATTERY_SERVICE_UUID_BYTES,
+            },
+            Attribute {
+                handle: Self::CHAR_DECL_HANDLE,
+                uuid: Uuid::Uuid16(CHARACTERISTIC_UUID),
+                value: &BATTERY_LEVEL_CHAR_DECL,
+            },
+            Attribute {
+                handle: Self::LEVEL_HANDLE,
+                uuid: Uuid::Uuid16(BATTERY_LEVEL_UUID),
+                value: level,
+            },
+        ]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn exchange_mtu_clamps_to_max_sdu_size() {
+        let level = &[42];
+        let attrs = BatteryServiceAttrs::attributes(level);
+        let mut server = GattServer::new(&attrs);
+
+        let pdu = [opcode::EXCHANGE_MTU_REQUEST, 0xFF, 0xFF];
+        let mut out = [0; MAX_SDU_SIZE];
+        let n = server.handle_att_pdu(&pdu, &mut out);
+
+        assert_eq!(out[0], opcode::EXCHANGE_MTU_RESPONSE);
+        assert_eq!(LittleEndian::read_u16(&out[1..3]), MAX_SDU_SIZE as u16);
+        assert_eq!(n, 3);
+    }
+
+    #[test]
+    fn read_by_group_type_finds_battery_service() {
+        let level = &[42];
+        let attrs = BatteryServiceAttrs::attributes(level);
+        let mut server = GattServer::new(&attrs);
+
+        let mut pdu = [0; 7];
+        pdu[0] = opcode::READ_BY_GROUP_TYPE_REQUEST;
+        LittleEndian::write_u16(&mut pdu[1..3], 1);
+        LittleEndian::write_u16(&mut pdu[3..5], 0xFFFF);
+        LittleEndian::write_u16(&mut pdu[5..7], PRIMARY_SERVICE_UUID);
+
+        let mut out = [0; MAX_SDU_SIZE];
+        let n = server.handle_att_pdu(&pdu, &mut out);
+
+        assert_eq!(out[0], opcode::READ_BY_GROUP_TYPE_RESPONSE);
+        assert_eq!(LittleEndian::read_u16(&out[2..4]), BatteryServiceAttrs::SERVICE_HANDLE);
+        assert_eq!(LittleEndian::read_u16(&out[4..6]), BatteryServiceAttrs::LEVEL_HANDLE);
+        assert_eq!(&out[6..8], &BATTERY_SERVICE_UUID_BYTES[..]);
+        assert_eq!(n, 8);
+    }
+
+    #[test]
+    fn read_by_type_stops_before_exceeding_the_mtu() {
+        // Four attributes that all match the same type and are all too big
+        // to fit more than one of them under the default 23-octet MTU:
+        // entry_size = 2 (handle) + 10 (value) = 12, and 2 (response header)
+        // + 12 + 12 = 26 > 23.
+        const VALUE: [u8; 10] = [0xAB; 10];
+        let attrs = [
+            Attribute { handle: 1, uuid: Uuid::Uuid16(0x1234), value: &VALUE },
+            Attribute { handle: 2, uuid: Uuid::Uuid16(0x1234), value: &VALUE },
+            Attribute { handle: 3, uuid: Uuid::Uuid16(0x1234), value: &VALUE },
+            Attribute { handle: 4, uuid: Uuid::Uuid16(0x1234), value: &VALUE },
+        ];
+        let mut server = GattServer::new(&attrs);
+
+        let mut pdu = [0; 7];
+        pdu[0] = opcode::READ_BY_TYPE_REQUEST;
+        LittleEndian::write_u16(&mut pdu[1..3], 1);
+        LittleEndian::write_u16(&mut pdu[3..5], 0xFFFF);
+        LittleEndian::write_u16(&mut pdu[5..7], 0x1234);
+
+        let mut out = [0; MAX_SDU_SIZE];
+        let n = server.handle_att_pdu(&pdu, &mut out);
+
+        assert_eq!(out[0], opcode::READ_BY_TYPE_RESPONSE);
+        assert_eq!(out[1], 12); // entry size: 2 + 10
+        assert_eq!(n, 2 + 12); // response header + exactly one entry
+        assert_eq!(LittleEndian::read_u16(&out[2..4]), 1);
+        assert_eq!(&out[4..14], &VALUE[..]);
+    }
+}
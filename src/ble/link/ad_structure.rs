@@ -0,0 +1,146 @@
+//! Advertising Data (AD) structures.
+//!
+//! An advertising or scan-response PDU carries a sequence of AD structures,
+//! each consisting of a length octet, an *AD Type* octet, and the type-specific
+//! data. This module provides a small, allocation-free representation of the
+//! subset of AD types the stack can emit, plus [`AdStructureIter`] to parse
+//! them back out of a received advertisement.
+
+use core::str;
+
+/// A list of device capabilities carried in the *Flags* AD structure.
+///
+/// Only the two LE discoverability bits and the *BR/EDR Not Supported* bit
+/// are modelled; the remaining bits are always transmitted as `0`.
+#[derive(Copy, Clone, Debug)]
+pub struct Flags(u8);
+
+impl Flags {
+    /// LE Limited Discoverable Mode.
+    const LE_LIMITED_DISCOVERABLE: u8 = 0b0000_0001;
+    /// LE General Discoverable Mode.
+    const LE_GENERAL_DISCOVERABLE: u8 = 0b0000_0010;
+    /// BR/EDR Not Supported.
+    const BR_EDR_NOT_SUPPORTED: u8 = 0b0000_0100;
+
+    /// Returns flags suitable for a connectable, generally discoverable device
+    /// that does not support classic Bluetooth.
+    pub fn discoverable() -> Self {
+        Flags(Self::LE_GENERAL_DISCOVERABLE | Self::BR_EDR_NOT_SUPPORTED)
+    }
+
+    /// Returns flags for a limited-discoverable device.
+    pub fn limited_discoverable() -> Self {
+        Flags(Self::LE_LIMITED_DISCOVERABLE | Self::BR_EDR_NOT_SUPPORTED)
+    }
+
+    /// Returns the raw flags octet as transmitted over the air.
+    pub fn to_u8(&self) -> u8 {
+        self.0
+    }
+}
+
+/// An Advertising Data structure.
+///
+/// This is the unit a user passes to [`start_advertise`]; the link layer
+/// serializes a slice of these into the advertising PDU payload.
+///
+/// [`start_advertise`]: super::LinkLayer::start_advertise
+#[derive(Copy, Clone, Debug)]
+pub enum AdStructure<'a> {
+    /// The *Flags* structure, indicating discoverability and supported modes.
+    Flags(Flags),
+
+    /// The device's complete local name, encoded as UTF-8.
+    CompleteLocalName(&'a str),
+
+    /// A shortened form of the device's local name.
+    ShortenedLocalName(&'a str),
+
+    /// An unknown or not-yet-supported AD structure, passed through verbatim as
+    /// `(ad_type, data)`.
+    Unknown(u8, &'a [u8]),
+}
+
+impl<'a> AdStructure<'a> {
+    /// The AD Type value used when flagging device capabilities.
+    const TYPE_FLAGS: u8 = 0x01;
+    /// The AD Type value for a shortened local name.
+    const TYPE_SHORTENED_LOCAL_NAME: u8 = 0x08;
+    /// The AD Type value for a complete local name.
+    const TYPE_COMPLETE_LOCAL_NAME: u8 = 0x09;
+
+    /// Appends the serialized form of this AD structure to `buf` and returns the
+    /// number of bytes written.
+    ///
+    /// The caller is responsible for ensuring that `buf` is large enough to hold
+    /// the structure; this panics otherwise.
+    pub fn encode(&self, buf: &mut [u8]) -> usize {
+        let (ad_type, data): (u8, &[u8]) = match self {
+            AdStructure::Flags(flags) => (Self::TYPE_FLAGS, &[flags.to_u8()]),
+            AdStructure::CompleteLocalName(name) => {
+                (Self::TYPE_COMPLETE_LOCAL_NAME, name.as_bytes())
+            }
+            AdStructure::ShortenedLocalName(name) => {
+                (Self::TYPE_SHORTENED_LOCAL_NAME, name.as_bytes())
+            }
+            AdStructure::Unknown(ty, data) => (*ty, data),
+        };
+
+        // Length octet covers the type octet plus the data.
+        let len = 1 + data.len();
+        buf[0] = len as u8;
+        buf[1] = ad_type;
+        buf[2..2 + data.len()].copy_from_slice(data);
+        1 + len
+    }
+}
+
+/// Iterates over the AD structures packed into an advertisement's payload.
+///
+/// Stops, without erroring, at the first malformed entry (a length octet
+/// that would run past the end of the data); anything before that point is
+/// still yielded.
+pub struct AdStructureIter<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> AdStructureIter<'a> {
+    /// Creates an iterator over the AD structures in `data`.
+    pub fn new(data: &'a [u8]) -> Self {
+        AdStructureIter { data }
+    }
+}
+
+impl<'a> Iterator for AdStructureIter<'a> {
+    type Item = AdStructure<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.data.is_empty() {
+            return None;
+        }
+
+        let len = self.data[0] as usize;
+        if len == 0 || 1 + len > self.data.len() {
+            self.data = &[];
+            return None;
+        }
+
+        let ad_type = self.data[1];
+        let value = &self.data[2..1 + len];
+        self.data = &self.data[1 + len..];
+
+        Some(match ad_type {
+            AdStructure::TYPE_FLAGS if value.len() == 1 => AdStructure::Flags(Flags(value[0])),
+            AdStructure::TYPE_COMPLETE_LOCAL_NAME => match str::from_utf8(value) {
+                Ok(name) => AdStructure::CompleteLocalName(name),
+                Err(_) => AdStructure::Unknown(ad_type, value),
+            },
+            AdStructure::TYPE_SHORTENED_LOCAL_NAME => match str::from_utf8(value) {
+                Ok(name) => AdStructure::ShortenedLocalName(name),
+                Err(_) => AdStructure::Unknown(ad_type, value),
+            },
+            _ => AdStructure::Unknown(ad_type, value),
+        })
+    }
+}
@@ -0,0 +1,99 @@
+//! Advertising-channel PDUs and the physical advertising channels.
+
+/// A physical advertising channel.
+///
+/// Only the three channels dedicated to advertising (37, 38 and 39) can be
+/// represented; these are the only channels on which advertising, scanning and
+/// connection establishment take place.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct AdvertisingChannel(u8);
+
+impl AdvertisingChannel {
+    /// The first advertising channel (2402 MHz).
+    pub fn first() -> Self {
+        AdvertisingChannel(37)
+    }
+
+    /// Returns the three advertising channels in ascending order.
+    pub fn all() -> [AdvertisingChannel; 3] {
+        [
+            AdvertisingChannel(37),
+            AdvertisingChannel(38),
+            AdvertisingChannel(39),
+        ]
+    }
+
+    /// Returns the next advertising channel, wrapping back to channel 37 after
+    /// channel 39.
+    pub fn cycle(self) -> Self {
+        match self.0 {
+            37 => AdvertisingChannel(38),
+            38 => AdvertisingChannel(39),
+            _ => AdvertisingChannel(37),
+        }
+    }
+
+    /// Returns the channel index (37, 38 or 39).
+    pub fn channel(self) -> u8 {
+        self.0
+    }
+
+    /// Returns the center frequency of this channel as an offset above
+    /// 2400 MHz, in MHz. This maps directly onto the RADIO `FREQUENCY` register.
+    pub fn freq_offset(self) -> u8 {
+        match self.0 {
+            37 => 2,
+            38 => 26,
+            _ => 80,
+        }
+    }
+}
+
+/// The type of an advertising-channel PDU, encoded in the low nibble of the PDU
+/// header.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum PduType {
+    /// Connectable, undirected advertising (`ADV_IND`).
+    AdvInd,
+    /// Connectable, directed advertising (`ADV_DIRECT_IND`).
+    AdvDirectInd,
+    /// Non-connectable, undirected advertising (`ADV_NONCONN_IND`).
+    AdvNonconnInd,
+    /// Scan request (`SCAN_REQ`).
+    ScanReq,
+    /// Scan response (`SCAN_RSP`).
+    ScanRsp,
+    /// Connection request (`CONNECT_REQ`).
+    ConnectReq,
+    /// Scannable, undirected advertising (`ADV_SCAN_IND`).
+    AdvScanInd,
+}
+
+impl PduType {
+    /// Decodes the PDU type from the low nibble of a PDU header.
+    pub fn from_header(header: u8) -> Option<Self> {
+        Some(match header & 0b1111 {
+            0b0000 => PduType::AdvInd,
+            0b0001 => PduType::AdvDirectInd,
+            0b0010 => PduType::AdvNonconnInd,
+            0b0011 => PduType::ScanReq,
+            0b0100 => PduType::ScanRsp,
+            0b0101 => PduType::ConnectReq,
+            0b0110 => PduType::AdvScanInd,
+            _ => return None,
+        })
+    }
+
+    /// Returns the 4-bit PDU type code for the header.
+    pub fn to_header(self) -> u8 {
+        match self {
+            PduType::AdvInd => 0b0000,
+            PduType::AdvDirectInd => 0b0001,
+            PduType::AdvNonconnInd => 0b0010,
+            PduType::ScanReq => 0b0011,
+            PduType::ScanRsp => 0b0100,
+            PduType::ConnectReq => 0b0101,
+            PduType::AdvScanInd => 0b0110,
+        }
+    }
+}
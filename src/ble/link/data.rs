@@ -0,0 +1,222 @@
+//! Data-channel PDUs and connection parameters.
+//!
+//! Once a connection is established, all communication happens on the 37 data
+//! channels using the framing defined here, and the `CONNECT_REQ` that set up
+//! the connection is parsed by [`ConnectRequest`].
+
+use byteorder::{ByteOrder, LittleEndian};
+
+/// The number of data channels defined by the specification.
+pub const NUM_DATA_CHANNELS: u8 = 37;
+
+/// A physical data channel (0..=36).
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct DataChannel(u8);
+
+impl DataChannel {
+    /// Creates a data channel from its index.
+    ///
+    /// Panics if `index` is not a valid data channel (i.e. `>= 37`).
+    pub fn new(index: u8) -> Self {
+        assert!(index < NUM_DATA_CHANNELS);
+        DataChannel(index)
+    }
+
+    /// Returns the channel index in `0..=36`.
+    pub fn index(self) -> u8 {
+        self.0
+    }
+
+    /// Returns the center frequency of this channel as an offset above
+    /// 2400 MHz, in MHz, suitable for the RADIO `FREQUENCY` register.
+    ///
+    /// Data channels occupy the gaps left by the advertising channels.
+    pub fn freq_offset(self) -> u8 {
+        // Channels 0..=10 live below advertising channel 38 (2426 MHz), the
+        // rest above it. Advertising channels 37/38/39 sit at 2402/2426/2480.
+        if self.0 <= 10 {
+            4 + self.0 * 2
+        } else {
+            6 + self.0 * 2
+        }
+    }
+}
+
+/// The *used channel map*: the set of data channels a connection is allowed to
+/// hop across, as negotiated in the `CONNECT_REQ`.
+///
+/// This drives Channel Selection Algorithm #1 together with the hop increment.
+#[derive(Copy, Clone, Debug)]
+pub struct ChannelMap {
+    /// 37-bit bitmap, one bit per data channel. Bit `n` set means channel `n`
+    /// is in use.
+    used: u64,
+    /// The number of channels marked as used. Cached to avoid recomputing the
+    /// population count on every connection event.
+    num_used: u8,
+}
+
+impl ChannelMap {
+    /// Builds a channel map from the 5-octet `ChM` field of a `CONNECT_REQ`.
+    pub fn from_raw(raw: [u8; 5]) -> Self {
+        let mut used = 0u64;
+        for (i, byte) in raw.iter().enumerate() {
+            used |= u64::from(*byte) << (i * 8);
+        }
+        // Only the low 37 bits are meaningful.
+        used &= (1 << NUM_DATA_CHANNELS) - 1;
+
+        ChannelMap {
+            used,
+            num_used: used.count_ones() as u8,
+        }
+    }
+
+    /// Returns `true` if data channel `index` is marked as used.
+    pub fn is_used(&self, index: u8) -> bool {
+        self.used & (1 << index) != 0
+    }
+
+    /// Returns the number of used channels.
+    pub fn num_used(&self) -> u8 {
+        self.num_used
+    }
+
+    /// Returns the `n`-th used channel (0-indexed), i.e. the remapping table
+    /// `usedChannelList[n]` from Channel Selection Algorithm #1.
+    ///
+    /// `n` must be smaller than [`num_used`](#method.num_used).
+    pub fn nth_used_channel(&self, n: u8) -> DataChannel {
+        let mut remaining = n;
+        for index in 0..NUM_DATA_CHANNELS {
+            if self.is_used(index) {
+                if remaining == 0 {
+                    return DataChannel::new(index);
+                }
+                remaining -= 1;
+            }
+        }
+        unreachable!("nth_used_channel called with n >= num_used");
+    }
+}
+
+/// The connection parameters carried in the `LLData` portion of a
+/// `CONNECT_REQ`.
+#[derive(Copy, Clone, Debug)]
+pub struct ConnectRequest {
+    /// Access address to use on the data channels.
+    pub access_address: u32,
+    /// Initial CRC value (24-bit, stored in the low bits of a `u32`).
+    pub crc_init: u32,
+    /// `transmitWindowSize` in 1.25 ms units.
+    pub win_size: u8,
+    /// `transmitWindowOffset` in 1.25 ms units.
+    pub win_offset: u16,
+    /// `connInterval` in 1.25 ms units.
+    pub interval: u16,
+    /// `connSlaveLatency`, in connection events.
+    pub latency: u16,
+    /// `connSupervisionTimeout` in 10 ms units.
+    pub timeout: u16,
+    /// The negotiated used-channel map.
+    pub channel_map: ChannelMap,
+    /// `hopIncrement`, the 5-bit increment applied each connection event
+    /// (5..=16).
+    pub hop: u8,
+    /// Master's sleep-clock accuracy, the upper 3 bits of the Hop/SCA field.
+    pub sca: u8,
+}
+
+impl ConnectRequest {
+    /// The size of the `LLData` payload of a `CONNECT_REQ`, in octets.
+    ///
+    /// `CONNECT_REQ` = InitA(6) + AdvA(6) + LLData(22).
+    pub const LL_DATA_SIZE: usize = 22;
+
+    /// Parses the `LLData` part of a `CONNECT_REQ` payload.
+    ///
+    /// `ll_data` must be the 22 octets following the initiator and advertiser
+    /// addresses. Returns `None` if the slice is too short, the channel map
+    /// marks fewer than two data channels as used, or the hop increment is
+    /// out of the valid 5..=16 range.
+    pub fn parse(ll_data: &[u8]) -> Option<Self> {
+        if ll_data.len() < Self::LL_DATA_SIZE {
+            return None;
+        }
+
+        let access_address = LittleEndian::read_u32(&ll_data[0..4]);
+        let crc_init = u32::from(ll_data[4])
+            | (u32::from(ll_data[5]) << 8)
+            | (u32::from(ll_data[6]) << 16);
+        let win_size = ll_data[7];
+        let win_offset = LittleEndian::read_u16(&ll_data[8..10]);
+        let interval = LittleEndian::read_u16(&ll_data[10..12]);
+        let latency = LittleEndian::read_u16(&ll_data[12..14]);
+        let timeout = LittleEndian::read_u16(&ll_data[14..16]);
+
+        let mut chm = [0u8; 5];
+        chm.copy_from_slice(&ll_data[16..21]);
+        let channel_map = ChannelMap::from_raw(chm);
+        // The spec mandates at least two used data channels; a smaller map
+        // would also make the CSA#1 remapping divide by zero.
+        if channel_map.num_used() < 2 {
+            return None;
+        }
+
+        let hop_sca = ll_data[21];
+        let hop = hop_sca & 0b0001_1111;
+        let sca = hop_sca >> 5;
+        if hop < 5 || hop > 16 {
+            return None;
+        }
+
+        Some(ConnectRequest {
+            access_address,
+            crc_init,
+            win_size,
+            win_offset,
+            interval,
+            latency,
+            timeout,
+            channel_map,
+            hop,
+            sca,
+        })
+    }
+}
+
+/// The Logical Link Identifier (LLID) of a data-channel PDU, taken from the two
+/// low bits of the PDU header.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Llid {
+    /// Reserved value; an LLID of `0b00` is not allowed.
+    Reserved,
+    /// Continuation of an L2CAP message, or an empty PDU.
+    DataCont,
+    /// Start of an L2CAP message (or a complete one that fits in a single PDU).
+    DataStart,
+    /// LL Control PDU.
+    Control,
+}
+
+impl Llid {
+    /// Extracts the LLID from a data-channel PDU header octet.
+    pub fn from_header(header: u8) -> Self {
+        match header & 0b11 {
+            0b01 => Llid::DataCont,
+            0b10 => Llid::DataStart,
+            0b11 => Llid::Control,
+            _ => Llid::Reserved,
+        }
+    }
+
+    /// Returns the 2-bit LLID code for a PDU header.
+    pub fn to_header(self) -> u8 {
+        match self {
+            Llid::Reserved => 0b00,
+            Llid::DataCont => 0b01,
+            Llid::DataStart => 0b10,
+            Llid::Control => 0b11,
+        }
+    }
+}
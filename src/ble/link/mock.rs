@@ -0,0 +1,79 @@
+//! A host-side mock `HardwareInterface`, used only by unit tests.
+//!
+//! [`MockHw`] lets [`LinkLayer`](super::LinkLayer) be driven from a test
+//! without any real radio or timer peripheral: [`MockTimer`] just remembers
+//! the last requested interrupt delay, and [`MockTx`] just remembers the last
+//! advertising-channel PDU it was handed and counts data-channel ones.
+
+use core::time::Duration;
+
+use super::advertising::AdvertisingChannel;
+use super::data::DataChannel;
+use super::{BleTimer, HardwareInterface, Transmitter, MAX_PDU_SIZE};
+
+/// Marker type tying [`MockTimer`] and [`MockTx`] together as a
+/// [`HardwareInterface`].
+pub enum MockHw {}
+
+impl HardwareInterface for MockHw {
+    type Timer = MockTimer;
+    type Tx = MockTx;
+}
+
+/// A [`BleTimer`] that just remembers the last requested interrupt delay.
+pub struct MockTimer {
+    pub last_duration: Option<Duration>,
+}
+
+impl MockTimer {
+    pub fn new() -> Self {
+        MockTimer { last_duration: None }
+    }
+}
+
+impl BleTimer for MockTimer {
+    fn configure_interrupt(&mut self, duration: Option<Duration>) {
+        self.last_duration = duration;
+    }
+}
+
+/// A [`Transmitter`] that records the last advertising-channel PDU it was
+/// asked to send and counts data-channel PDUs, instead of actually
+/// transmitting anything.
+pub struct MockTx {
+    /// `(header, payload buffer, payload length, channel)` of the last
+    /// advertising-channel PDU
+    /// handed to [`transmit_advertising`](Transmitter::transmit_advertising).
+    pub last_advertising: Option<(u8, [u8; MAX_PDU_SIZE], usize, AdvertisingChannel)>,
+    /// Number of times [`transmit_data`](Transmitter::transmit_data) was
+    /// called.
+    pub data_tx_count: usize,
+}
+
+impl MockTx {
+    pub fn new() -> Self {
+        MockTx {
+            last_advertising: None,
+            data_tx_count: 0,
+        }
+    }
+}
+
+impl Transmitter for MockTx {
+    fn transmit_advertising(&mut self, header: u8, payload: &[u8], channel: AdvertisingChannel) {
+        let mut buf = [0; MAX_PDU_SIZE];
+        buf[..payload.len()].copy_from_slice(payload);
+        self.last_advertising = Some((header, buf, payload.len(), channel));
+    }
+
+    fn transmit_data(
+        &mut self,
+        _access_address: u32,
+        _crc_init: u32,
+        _header: u8,
+        _payload: &[u8],
+        _channel: DataChannel,
+    ) {
+        self.data_tx_count += 1;
+    }
+}
@@ -0,0 +1,695 @@
+//! The Link Layer.
+//!
+//! The Link Layer is the lowest protocol layer exposed by this stack. It
+//! manages the advertising and connection state machines and selects the data
+//! channel for each connection event using Channel Selection Algorithm #1.
+//!
+//! The layer is decoupled from any concrete radio or timer through the
+//! [`HardwareInterface`] trait: a [`LinkLayer`] is parameterized over the
+//! hardware it runs on, sends packets through a [`Transmitter`], and schedules
+//! work through a [`BleTimer`]. Reception and the actual register pokes live in
+//! the per-platform glue (the nrf51 [`Baseband`]), which lets the core stack be
+//! reused on other nRF parts without touching it.
+//!
+//! [`Baseband`]: ::radio::Baseband
+
+pub mod ad_structure;
+pub mod advertising;
+pub mod data;
+#[cfg(test)]
+mod mock;
+pub mod queue;
+pub mod scan;
+
+use core::marker::PhantomData;
+use core::time::Duration;
+
+use self::ad_structure::AdStructure;
+use self::advertising::{AdvertisingChannel, PduType};
+use self::data::{ConnectRequest, DataChannel, NUM_DATA_CHANNELS};
+use self::queue::Producer;
+
+/// The largest PDU payload the stack handles, in octets.
+pub const MAX_PDU_SIZE: usize = 37;
+
+/// A radio that can transmit BLE packets on behalf of the link layer.
+///
+/// The link layer fills in the header and payload and picks the channel; the
+/// implementation is responsible for the access address, whitening and CRC
+/// configuration as well as the actual transmission.
+pub trait Transmitter {
+    /// Transmits an advertising-channel PDU with the given `header` octet and
+    /// `payload` on the advertising `channel`.
+    fn transmit_advertising(&mut self, header: u8, payload: &[u8], channel: AdvertisingChannel);
+
+    /// Transmits a data-channel PDU during a connection event.
+    fn transmit_data(
+        &mut self,
+        access_address: u32,
+        crc_init: u32,
+        header: u8,
+        payload: &[u8],
+        channel: DataChannel,
+    );
+}
+
+/// A timer the link layer uses to schedule its next wakeup.
+pub trait BleTimer {
+    /// Arms the timer to raise an interrupt after `duration`, or stops it when
+    /// `duration` is `None`.
+    fn configure_interrupt(&mut self, duration: Option<Duration>);
+}
+
+/// Ties together the hardware a [`LinkLayer`] runs on.
+///
+/// Implementors pick a concrete [`BleTimer`] and [`Transmitter`]; the nrf51
+/// implementation lives in the [`radio`] module, but other nRF parts can supply
+/// their own without touching the core stack.
+///
+/// [`radio`]: ::radio
+pub trait HardwareInterface {
+    /// The timer driving connection-event and advertising timing.
+    type Timer: BleTimer;
+    /// The radio used to transmit packets.
+    type Tx: Transmitter;
+}
+
+/// The kind of a BLE device address.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum AddressKind {
+    /// A public address, assigned by the IEEE.
+    Public,
+    /// A random address.
+    Random,
+}
+
+/// A 48-bit BLE device address and its kind.
+#[derive(Copy, Clone, Debug)]
+pub struct DeviceAddress {
+    bytes: [u8; 6],
+    kind: AddressKind,
+}
+
+impl DeviceAddress {
+    /// Creates a device address from its 6 raw octets (little-endian, as stored
+    /// in the FICR) and its kind.
+    pub fn new(bytes: [u8; 6], kind: AddressKind) -> Self {
+        DeviceAddress { bytes, kind }
+    }
+
+    /// Returns the raw address octets.
+    pub fn bytes(&self) -> &[u8; 6] {
+        &self.bytes
+    }
+
+    /// Returns whether this is a public or random address.
+    pub fn kind(&self) -> AddressKind {
+        self.kind
+    }
+}
+
+/// The reception the radio should set up after a link-layer update.
+///
+/// Transmission is driven directly through the [`Transmitter`]; this command
+/// only describes what the radio should *listen* for until the next update.
+#[derive(Copy, Clone, Debug)]
+pub enum RadioCmd {
+    /// Stop the radio.
+    Off,
+
+    /// Listen on an advertising `channel`: while advertising, for a
+    /// `SCAN_REQ` or `CONNECT_REQ` addressed to us (the advertisement itself
+    /// was already transmitted); while scanning, for other devices'
+    /// advertisements.
+    ListenAdvertising { channel: AdvertisingChannel },
+
+    /// Listen on a data channel for the next connection event.
+    ListenData {
+        /// Channel to receive/transmit on.
+        channel: DataChannel,
+        /// Access address of the connection.
+        access_address: u32,
+        /// CRC initialization value of the connection.
+        crc_init: u32,
+    },
+}
+
+/// The result of a link-layer update: what the radio should do, and when to
+/// schedule the next timer interrupt.
+#[derive(Copy, Clone, Debug)]
+pub struct Cmd {
+    /// When the timer should next fire, or `None` to leave the timer stopped.
+    pub next_update: Option<Duration>,
+    /// The radio action to take until then.
+    pub radio: RadioCmd,
+}
+
+/// Per-connection state for a peripheral-role connection.
+struct Connection {
+    /// Parameters negotiated in the `CONNECT_REQ`.
+    params: ConnectRequest,
+    /// The connection interval, precomputed from `params.interval`.
+    conn_interval: Duration,
+    /// `connSupervisionTimeout`, precomputed from `params.timeout`.
+    supervision_timeout: Duration,
+    /// Time elapsed since the last PDU was received from the central,
+    /// accumulated one `conn_interval` at a time. The connection is torn
+    /// down once this reaches `supervision_timeout`.
+    time_since_last_rx: Duration,
+    /// `lastUnmappedChannel` of Channel Selection Algorithm #1.
+    last_unmapped_channel: u8,
+    /// The connection event counter, incremented on every event.
+    event_counter: u16,
+}
+
+impl Connection {
+    /// Selects the data channel for the next connection event using Channel
+    /// Selection Algorithm #1, advancing the hopping state.
+    fn next_channel(&mut self) -> DataChannel {
+        // unmappedChannel = (lastUnmappedChannel + hopIncrement) mod 37
+        let unmapped = (self.last_unmapped_channel + self.params.hop) % NUM_DATA_CHANNELS;
+        self.last_unmapped_channel = unmapped;
+
+        let map = &self.params.channel_map;
+        if map.is_used(unmapped) {
+            DataChannel::new(unmapped)
+        } else {
+            // Remap into the list of used channels.
+            let remapping_index = unmapped % map.num_used();
+            map.nth_used_channel(remapping_index)
+        }
+    }
+}
+
+/// The state machine of the link layer.
+enum State {
+    /// Idle; the radio is off.
+    Standby,
+
+    /// Advertising an `ADV_IND` on the three advertising channels.
+    Advertising {
+        /// Advertising channel of the next event.
+        channel: AdvertisingChannel,
+        /// Advertising interval.
+        interval: Duration,
+    },
+
+    /// In a connection as the peripheral.
+    Connection(Connection),
+
+    /// Scanning for other devices' advertisements.
+    Scanning {
+        /// Advertising channel to listen on next.
+        channel: AdvertisingChannel,
+        /// How long to listen on one channel before moving to the next.
+        interval: Duration,
+        /// Whether a scannable advertisement gets an immediate `SCAN_REQ`.
+        active: bool,
+    },
+}
+
+/// The BLE Link Layer.
+///
+/// A `LinkLayer` is created with the device's own address and then driven into
+/// an active role via [`start_advertise`]. The [`Baseband`] polls it through
+/// [`update`] (on timer interrupts) and [`process_packet`] (on received
+/// packets). It is generic over the [`HardwareInterface`] it runs on, which
+/// keeps the state machine itself free of any nrf51-specific register
+/// access.
+///
+/// [`start_advertise`]: #method.start_advertise
+/// [`update`]: #method.update
+/// [`process_packet`]: #method.process_packet
+/// [`Baseband`]: ::radio::Baseband
+pub struct LinkLayer<H: HardwareInterface> {
+    dev_addr: DeviceAddress,
+    state: State,
+    /// Serialized advertising PDU payload (AdvA followed by AD structures).
+    adv_payload: [u8; MAX_PDU_SIZE],
+    /// Number of valid bytes in `adv_payload`.
+    adv_payload_len: usize,
+    /// Producer half of the queue scan reports are delivered through, set by
+    /// [`start_scan`](#method.start_scan).
+    scan_reports: Option<Producer<'static>>,
+    _hw: PhantomData<H>,
+}
+
+impl<H: HardwareInterface> LinkLayer<H> {
+    /// Creates a new, idle link layer for a device with the given address.
+    pub fn new(dev_addr: DeviceAddress) -> Self {
+        LinkLayer {
+            dev_addr,
+            state: State::Standby,
+            adv_payload: [0; MAX_PDU_SIZE],
+            adv_payload_len: 0,
+            scan_reports: None,
+            _hw: PhantomData,
+        }
+    }
+
+    /// Starts advertising an `ADV_IND` containing `data`, repeating every
+    /// `interval`.
+    ///
+    /// While advertising, the device is connectable: an incoming `CONNECT_REQ`
+    /// will move it into the connected state (see [`process_adv_packet`]).
+    ///
+    /// [`process_adv_packet`]: #method.process_adv_packet
+    pub fn start_advertise(&mut self, interval: Duration, data: &[AdStructure]) {
+        // The advertising payload starts with our own address (AdvA).
+        self.adv_payload[..6].copy_from_slice(self.dev_addr.bytes());
+        let mut len = 6;
+        for ad in data {
+            len += ad.encode(&mut self.adv_payload[len..]);
+        }
+        assert!(len <= MAX_PDU_SIZE);
+        self.adv_payload_len = len;
+
+        self.state = State::Advertising {
+            channel: AdvertisingChannel::first(),
+            interval,
+        };
+    }
+
+    /// Starts scanning for advertisements on the three advertising channels,
+    /// switching to the next one every `interval`.
+    ///
+    /// Every `ADV_IND`, `ADV_NONCONN_IND`, `ADV_SCAN_IND` and `SCAN_RSP`
+    /// received is delivered through `scan_reports`' producer half as a frame
+    /// `idle()` can decode with [`scan::ScanReport::parse`]. If `active` is
+    /// set, a scannable advertisement (`ADV_IND`/`ADV_SCAN_IND`) additionally
+    /// gets an immediate `SCAN_REQ` in reply, and its `SCAN_RSP` is reported
+    /// the same way once it arrives.
+    pub fn start_scan(&mut self, interval: Duration, active: bool, scan_reports: Producer<'static>) {
+        self.scan_reports = Some(scan_reports);
+        self.state = State::Scanning {
+            channel: AdvertisingChannel::first(),
+            interval,
+            active,
+        };
+    }
+
+    /// Returns the header octet of the advertising PDU to broadcast.
+    fn advertising_header(&self) -> u8 {
+        // ADV_IND, with the TxAdd bit reflecting our address kind.
+        let mut header = PduType::AdvInd.to_header();
+        if self.dev_addr.kind() == AddressKind::Random {
+            header |= 0b0100_0000; // TxAdd
+        }
+        header
+    }
+
+    /// Returns whether a `CONNECT_REQ`'s AdvA (and the header's RxAdd bit)
+    /// identify us as the intended advertiser, rather than some other
+    /// advertiser sharing the same advertising access address.
+    fn adv_addr_matches(&self, header: u8, adv_a: &[u8]) -> bool {
+        let rx_add_random = header & 0b1000_0000 != 0; // RxAdd
+        let kind_matches = rx_add_random == (self.dev_addr.kind() == AddressKind::Random);
+        kind_matches && adv_a == &self.dev_addr.bytes()[..]
+    }
+
+    /// Advances the link-layer state machine on a timer interrupt.
+    ///
+    /// Any outgoing packet is transmitted through `tx`; the returned [`Cmd`]
+    /// describes what the radio should then listen for and when the next update
+    /// is due.
+    pub fn update(&mut self, tx: &mut H::Tx) -> Cmd {
+        // Advertising transmits before listening; do that first so we don't
+        // hold a mutable borrow of `self.state` across the `tx` call.
+        if let State::Advertising { channel, interval } = self.state {
+            let header = self.advertising_header();
+            tx.transmit_advertising(header, &self.adv_payload[..self.adv_payload_len], channel);
+
+            if let State::Advertising { channel: next, .. } = &mut self.state {
+                *next = channel.cycle();
+            }
+            return Cmd {
+                next_update: Some(interval),
+                radio: RadioCmd::ListenAdvertising { channel },
+            };
+        }
+
+        match &mut self.state {
+            State::Standby => Cmd {
+                next_update: None,
+                radio: RadioCmd::Off,
+            },
+
+            State::Connection(conn) => {
+                conn.time_since_last_rx += conn.conn_interval;
+                if conn.time_since_last_rx >= conn.supervision_timeout {
+                    // The central hasn't been heard from within
+                    // connSupervisionTimeout; the connection is lost.
+                    self.state = State::Standby;
+                    return Cmd {
+                        next_update: None,
+                        radio: RadioCmd::Off,
+                    };
+                }
+
+                let channel = conn.next_channel();
+                conn.event_counter = conn.event_counter.wrapping_add(1);
+                Cmd {
+                    next_update: Some(conn.conn_interval),
+                    radio: RadioCmd::ListenData {
+                        channel,
+                        access_address: conn.params.access_address,
+                        crc_init: conn.params.crc_init,
+                    },
+                }
+            }
+
+            State::Scanning { channel, interval, .. } => {
+                let this_channel = *channel;
+                *channel = channel.cycle();
+                Cmd {
+                    next_update: Some(*interval),
+                    radio: RadioCmd::ListenAdvertising { channel: this_channel },
+                }
+            }
+
+            State::Advertising { .. } => unreachable!("handled above"),
+        }
+    }
+
+    /// Processes an advertising-channel PDU received while advertising or
+    /// scanning.
+    ///
+    /// When a valid `CONNECT_REQ` *addressed to us* (its AdvA and RxAdd match
+    /// our own device address) arrives while advertising, the link layer
+    /// transitions into the connected state and returns a [`Cmd`] that starts
+    /// servicing connection events. Any other PDU (including a malformed
+    /// request, or a `CONNECT_REQ` meant for a different advertiser overheard
+    /// on the shared advertising access address) leaves the state unchanged
+    /// and keeps advertising. While scanning, this instead reports the
+    /// advertisement and, for active scanning, replies to a scannable one
+    /// with a `SCAN_REQ`; see [`process_scan_packet`].
+    ///
+    /// `rssi` is the signal strength the packet was received at and `tx` lets
+    /// a `SCAN_REQ` be sent back within the required inter-frame spacing.
+    ///
+    /// [`process_scan_packet`]: #method.process_scan_packet
+    pub fn process_adv_packet(&mut self, header: u8, payload: &[u8], rssi: i8, tx: &mut H::Tx) -> Cmd {
+        if let State::Advertising { channel, .. } = self.state {
+            if let Some(PduType::ConnectReq) = PduType::from_header(header) {
+                // CONNECT_REQ payload: InitA(6) + AdvA(6) + LLData(22).
+                if payload.len() >= 12 && self.adv_addr_matches(header, &payload[6..12]) {
+                    if let Some(params) = ConnectRequest::parse(&payload[12..]) {
+                        return self.connect(params);
+                    }
+                }
+            }
+            // Not a usable connection request; keep listening on this channel.
+            return Cmd {
+                next_update: None,
+                radio: RadioCmd::ListenAdvertising { channel },
+            };
+        }
+
+        if let State::Scanning { channel, active, .. } = self.state {
+            return self.process_scan_packet(header, payload, rssi, channel, active, tx);
+        }
+
+        Cmd {
+            next_update: None,
+            radio: RadioCmd::Off,
+        }
+    }
+
+    /// Processes an advertising-channel PDU received while scanning on
+    /// `channel`.
+    ///
+    /// `ADV_IND`, `ADV_NONCONN_IND`, `ADV_SCAN_IND` and `SCAN_RSP` PDUs are
+    /// reported through [`start_scan`]'s `scan_reports` queue. If `active`
+    /// scanning is on and the PDU is scannable (`ADV_IND`/`ADV_SCAN_IND`), a
+    /// `SCAN_REQ` is sent back immediately so its `SCAN_RSP` arrives within
+    /// this same listening window.
+    ///
+    /// [`start_scan`]: #method.start_scan
+    fn process_scan_packet(
+        &mut self,
+        header: u8,
+        payload: &[u8],
+        rssi: i8,
+        channel: AdvertisingChannel,
+        active: bool,
+        tx: &mut H::Tx,
+    ) -> Cmd {
+        let pdu_type = PduType::from_header(header);
+        let scannable = pdu_type == Some(PduType::AdvInd) || pdu_type == Some(PduType::AdvScanInd);
+        let reportable = scannable
+            || pdu_type == Some(PduType::AdvNonconnInd)
+            || pdu_type == Some(PduType::ScanRsp);
+
+        if reportable && payload.len() >= 6 {
+            let kind = if header & 0b0100_0000 != 0 {
+                AddressKind::Random
+            } else {
+                AddressKind::Public
+            };
+            let mut addr_bytes = [0; 6];
+            addr_bytes.copy_from_slice(&payload[..6]);
+            let addr = DeviceAddress::new(addr_bytes, kind);
+
+            if let Some(queue) = &mut self.scan_reports {
+                let mut frame = [0; scan::MAX_REPORT_SIZE];
+                let len = scan::encode(&mut frame, addr, rssi, &payload[6..]);
+                queue.enqueue(&frame[..len]);
+            }
+
+            if active && scannable {
+                let mut scan_header = PduType::ScanReq.to_header();
+                if self.dev_addr.kind() == AddressKind::Random {
+                    scan_header |= 0b0100_0000; // TxAdd
+                }
+                if kind == AddressKind::Random {
+                    scan_header |= 0b1000_0000; // RxAdd
+                }
+
+                // SCAN_REQ payload: ScanA(6) + AdvA(6).
+                let mut req = [0; 12];
+                req[..6].copy_from_slice(self.dev_addr.bytes());
+                req[6..12].copy_from_slice(addr.bytes());
+                tx.transmit_advertising(scan_header, &req, channel);
+            }
+        }
+
+        Cmd {
+            next_update: None,
+            radio: RadioCmd::ListenAdvertising { channel },
+        }
+    }
+
+    /// Processes a received PDU, dispatching on the current state.
+    ///
+    /// While advertising or scanning this hands the PDU to
+    /// [`process_adv_packet`]; during a connection it goes to the
+    /// data-channel path instead. The `Baseband` calls this for every
+    /// received packet regardless of state.
+    ///
+    /// [`process_adv_packet`]: #method.process_adv_packet
+    pub fn process_packet(&mut self, header: u8, payload: &[u8], rssi: i8, tx: &mut H::Tx) -> Cmd {
+        match self.state {
+            State::Connection(_) => self.process_data_packet(header, payload),
+            _ => self.process_adv_packet(header, payload, rssi, tx),
+        }
+    }
+
+    /// Processes a data-channel PDU received during a connection event.
+    ///
+    /// Any PDU received from the central, including an empty keepalive one,
+    /// resets the connection supervision timeout tracked in [`update`]. The
+    /// next connection event has already been scheduled by [`update`], so
+    /// this only parks the radio until then; it must *not* advance the
+    /// channel hop, which happens once per event in [`update`]. Outside a
+    /// connection it returns the same [`Cmd`] (radio off, timer untouched)
+    /// and changes nothing.
+    ///
+    /// [`update`]: #method.update
+    pub fn process_data_packet(&mut self, _header: u8, _payload: &[u8]) -> Cmd {
+        if let State::Connection(conn) = &mut self.state {
+            conn.time_since_last_rx = Duration::from_secs(0);
+        }
+
+        // Leave the timer untouched (`next_update: None`) so the event spacing
+        // set up in `update` is preserved, and idle the radio until the next
+        // event.
+        Cmd {
+            next_update: None,
+            radio: RadioCmd::Off,
+        }
+    }
+
+    /// Transitions from advertising into a peripheral-role connection.
+    fn connect(&mut self, params: ConnectRequest) -> Cmd {
+        // connInterval is expressed in units of 1.25 ms, connSupervisionTimeout
+        // in units of 10 ms.
+        let conn_interval = Duration::from_micros(u64::from(params.interval) * 1_250);
+        let supervision_timeout = Duration::from_millis(u64::from(params.timeout) * 10);
+
+        let mut conn = Connection {
+            params,
+            conn_interval,
+            supervision_timeout,
+            time_since_last_rx: Duration::from_secs(0),
+            // Per the spec, lastUnmappedChannel starts at 0 so the first event
+            // uses channel `hopIncrement`.
+            last_unmapped_channel: 0,
+            event_counter: 0,
+        };
+
+        let channel = conn.next_channel();
+        let access_address = conn.params.access_address;
+        let crc_init = conn.params.crc_init;
+
+        // The first connection event happens after transmitWindowOffset; we
+        // approximate the window start with the connection interval here.
+        // transmitWindowOffset and transmitWindowSize are not used, and every
+        // later event is spaced exactly one connection interval apart.
+        self.state = State::Connection(conn);
+
+        Cmd {
+            next_update: Some(conn_interval),
+            radio: RadioCmd::ListenData {
+                channel,
+                access_address,
+                crc_init,
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use byteorder::{ByteOrder, LittleEndian};
+
+    use super::data::ChannelMap;
+    use super::mock::{MockHw, MockTx};
+    use super::*;
+
+    /// Builds a `Connection` with the given hop increment and channel map, all
+    /// other parameters set to arbitrary but valid values.
+    fn conn_with(hop: u8, channel_map: ChannelMap) -> Connection {
+        Connection {
+            params: ConnectRequest {
+                access_address: 0x8E89_BED6,
+                crc_init: 0x55_5555,
+                win_size: 2,
+                win_offset: 0,
+                interval: 80,
+                latency: 0,
+                timeout: 500,
+                channel_map,
+                hop,
+                sca: 0,
+            },
+            conn_interval: Duration::from_millis(100),
+            supervision_timeout: Duration::from_secs(5),
+            time_since_last_rx: Duration::from_secs(0),
+            last_unmapped_channel: 0,
+            event_counter: 0,
+        }
+    }
+
+    #[test]
+    fn next_channel_uses_the_unmapped_channel_when_its_used() {
+        // All 37 channels used: the unmapped channel is never remapped.
+        let map = ChannelMap::from_raw([0xFF, 0xFF, 0xFF, 0xFF, 0x1F]);
+        let mut conn = conn_with(5, map);
+        assert_eq!(conn.next_channel().index(), 5);
+        assert_eq!(conn.next_channel().index(), 10);
+        assert_eq!(conn.next_channel().index(), 15);
+    }
+
+    #[test]
+    fn next_channel_remaps_unused_channels() {
+        // Only channels 0..=10 are marked used, so an unmapped channel
+        // outside that range must go through `nth_used_channel`.
+        let map = ChannelMap::from_raw([0xFF, 0x07, 0x00, 0x00, 0x00]);
+        let mut conn = conn_with(16, map);
+
+        // unmappedChannel = 16, unused -> remaps to the (16 % 11) = 5th used
+        // channel, i.e. channel 5.
+        assert_eq!(conn.next_channel().index(), 5);
+        // unmappedChannel = 32, unused -> remaps to the (32 % 11) = 10th used
+        // channel, i.e. channel 10.
+        assert_eq!(conn.next_channel().index(), 10);
+    }
+
+    #[test]
+    fn connect_req_addressed_to_us_starts_a_connection() {
+        let dev_addr = DeviceAddress::new([1, 2, 3, 4, 5, 6], AddressKind::Public);
+        let mut ll: LinkLayer<MockHw> = LinkLayer::new(dev_addr);
+        ll.start_advertise(Duration::from_millis(100), &[]);
+
+        // CONNECT_REQ payload: InitA(6) + AdvA(6) + LLData(22).
+        let mut payload = [0u8; 34];
+        payload[..6].copy_from_slice(&[9, 9, 9, 9, 9, 9]); // InitA, irrelevant here
+        payload[6..12].copy_from_slice(dev_addr.bytes());
+
+        let ll_data = &mut payload[12..34];
+        LittleEndian::write_u32(&mut ll_data[0..4], 0x8E89_BED6); // access address
+        ll_data[4..7].copy_from_slice(&[0x55, 0x55, 0x55]); // CRC init
+        ll_data[7] = 2; // transmitWindowSize
+        LittleEndian::write_u16(&mut ll_data[8..10], 0); // transmitWindowOffset
+        LittleEndian::write_u16(&mut ll_data[10..12], 80); // connInterval
+        LittleEndian::write_u16(&mut ll_data[12..14], 0); // connSlaveLatency
+        LittleEndian::write_u16(&mut ll_data[14..16], 500); // connSupervisionTimeout
+        ll_data[16..21].copy_from_slice(&[0xFF, 0xFF, 0xFF, 0xFF, 0x1F]); // ChM, all used
+        ll_data[21] = 5; // hopIncrement = 5, masterSCA = 0
+
+        let header = PduType::ConnectReq.to_header(); // our address is public: no RxAdd bit
+        let mut tx = MockTx::new();
+        let cmd = ll.process_adv_packet(header, &payload, -40, &mut tx);
+
+        match cmd.radio {
+            RadioCmd::ListenData { channel, access_address, .. } => {
+                assert_eq!(access_address, 0x8E89_BED6);
+                // First CSA#1 event: lastUnmappedChannel 0 + hop 5 = channel 5.
+                assert_eq!(channel.index(), 5);
+            }
+            other => panic!("expected to enter a connection, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn supervision_timeout_tears_down_an_unresponsive_connection() {
+        let map = ChannelMap::from_raw([0xFF, 0xFF, 0xFF, 0xFF, 0x1F]);
+        let mut ll: LinkLayer<MockHw> =
+            LinkLayer::new(DeviceAddress::new([1, 2, 3, 4, 5, 6], AddressKind::Random));
+        ll.state = State::Connection(conn_with(5, map));
+        let mut tx = MockTx::new();
+
+        // supervision_timeout (5s) / conn_interval (100ms) = 50 events.
+        let mut cmd = None;
+        for _ in 0..50 {
+            cmd = Some(ll.update(&mut tx));
+        }
+
+        match cmd.unwrap().radio {
+            RadioCmd::Off => {}
+            other => panic!("expected the connection to be torn down, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn receiving_a_data_packet_resets_the_supervision_timeout() {
+        let map = ChannelMap::from_raw([0xFF, 0xFF, 0xFF, 0xFF, 0x1F]);
+        let mut ll: LinkLayer<MockHw> =
+            LinkLayer::new(DeviceAddress::new([1, 2, 3, 4, 5, 6], AddressKind::Random));
+        ll.state = State::Connection(conn_with(5, map));
+        let mut tx = MockTx::new();
+
+        // Run down to one event short of the timeout...
+        for _ in 0..49 {
+            ll.update(&mut tx);
+        }
+        // ...then hear from the central, which must reset the tracker.
+        ll.process_data_packet(0, &[]);
+        let cmd = ll.update(&mut tx);
+
+        match cmd.radio {
+            RadioCmd::ListenData { .. } => {}
+            other => panic!("connection should still be alive, got {:?}", other),
+        }
+    }
+}
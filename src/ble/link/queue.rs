@@ -0,0 +1,151 @@
+//! A lock-free, single-producer/single-consumer queue of length-prefixed PDUs.
+//!
+//! [`PacketQueue`] is the handoff point between the radio ISR (the producer
+//! for received PDUs, the consumer for outgoing ones) and the [`Responder`]
+//! polled from `idle()`, so that PDU parsing and answer construction never run
+//! at interrupt priority. [`split`] hands out a [`Producer`]/[`Consumer`]
+//! pair, each restricted to the operations its side is allowed to perform
+//! wait-free and without locks.
+//!
+//! [`Responder`]: ::responder::Responder
+//! [`split`]: PacketQueue::split
+
+use core::cell::UnsafeCell;
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+use super::MAX_PDU_SIZE;
+
+/// Capacity of the underlying byte ring, in octets.
+///
+/// Must be a power of two so indices can be masked instead of computed with a
+/// division. Large enough to hold a handful of maximum-sized frames, each
+/// prefixed with a 1-octet length.
+const QUEUE_CAPACITY: usize = 128;
+
+/// The largest Link Layer PDU frame: header octet + a full payload.
+pub const MAX_FRAME_SIZE: usize = 1 + MAX_PDU_SIZE;
+
+/// A fixed-capacity ring of length-prefixed PDUs, shared between one producer
+/// and one consumer.
+///
+/// `head` is only ever written by the [`Consumer`] and `tail` only by the
+/// [`Producer`]; each side only reads the other's index, which is what makes
+/// [`enqueue`][Producer::enqueue]/[`dequeue`][Consumer::dequeue] safe without a
+/// lock.
+pub struct PacketQueue {
+    buf: UnsafeCell<[u8; QUEUE_CAPACITY]>,
+    /// Index of the next byte to dequeue. Monotonically increasing; indices
+    /// into `buf` are obtained by masking with `QUEUE_CAPACITY - 1`.
+    head: AtomicUsize,
+    /// Index of the next free byte. Monotonically increasing, same masking as
+    /// `head`.
+    tail: AtomicUsize,
+}
+
+// `buf` is only ever accessed through the disjoint byte ranges handed to the
+// `Producer` and `Consumer` halves, which is what makes sharing it across
+// those two safe.
+unsafe impl Sync for PacketQueue {}
+
+impl PacketQueue {
+    /// Creates an empty queue.
+    pub const fn new() -> Self {
+        PacketQueue {
+            buf: UnsafeCell::new([0; QUEUE_CAPACITY]),
+            head: AtomicUsize::new(0),
+            tail: AtomicUsize::new(0),
+        }
+    }
+
+    /// Splits the queue into its producer and consumer halves.
+    ///
+    /// Only call this once per queue; a second split would hand out a second
+    /// `Producer` or `Consumer`, breaking the single-producer/single-consumer
+    /// invariant the implementation relies on.
+    pub fn split(&self) -> (Producer, Consumer) {
+        (Producer { queue: self }, Consumer { queue: self })
+    }
+}
+
+/// The producing half of a [`PacketQueue`].
+pub struct Producer<'a> {
+    queue: &'a PacketQueue,
+}
+
+impl<'a> Producer<'a> {
+    /// Enqueues `pdu`, prefixed with its length.
+    ///
+    /// Returns `false` without writing anything if `pdu` does not fit in the
+    /// remaining queue capacity.
+    ///
+    /// Wait-free: this only ever loads the consumer's `head` once and performs
+    /// a bounded number of writes.
+    ///
+    /// Panics if `pdu`, plus its length prefix, could never fit in the queue
+    /// at all (as opposed to just not fitting right now); callers enqueueing
+    /// frames other than Link Layer PDUs, e.g. scan reports, are not bound by
+    /// [`MAX_FRAME_SIZE`] and only need to respect this bound.
+    pub fn enqueue(&mut self, pdu: &[u8]) -> bool {
+        assert!(1 + pdu.len() <= QUEUE_CAPACITY);
+        let needed = 1 + pdu.len();
+
+        let tail = self.queue.tail.load(Ordering::Relaxed);
+        let head = self.queue.head.load(Ordering::Acquire);
+        if QUEUE_CAPACITY - (tail.wrapping_sub(head)) < needed {
+            return false;
+        }
+
+        let buf = unsafe { &mut *self.queue.buf.get() };
+        write_byte(buf, tail, pdu.len() as u8);
+        for (i, &byte) in pdu.iter().enumerate() {
+            write_byte(buf, tail.wrapping_add(1 + i), byte);
+        }
+
+        self.queue.tail.store(tail.wrapping_add(needed), Ordering::Release);
+        true
+    }
+}
+
+/// The consuming half of a [`PacketQueue`].
+pub struct Consumer<'a> {
+    queue: &'a PacketQueue,
+}
+
+impl<'a> Consumer<'a> {
+    /// Dequeues the next PDU into `out`, returning its length, or `None` if
+    /// the queue is empty.
+    ///
+    /// Panics if `out` is smaller than the dequeued frame; callers should size
+    /// `out` to the largest frame their producer enqueues, e.g.
+    /// [`MAX_FRAME_SIZE`] for Link Layer PDUs or
+    /// [`MAX_REPORT_SIZE`](super::scan::MAX_REPORT_SIZE) for scan reports.
+    ///
+    /// Wait-free: this only ever loads the producer's `tail` once and performs
+    /// a bounded number of reads.
+    pub fn dequeue(&mut self, out: &mut [u8]) -> Option<usize> {
+        let head = self.queue.head.load(Ordering::Relaxed);
+        let tail = self.queue.tail.load(Ordering::Acquire);
+        if head == tail {
+            return None;
+        }
+
+        let buf = unsafe { &*self.queue.buf.get() };
+        let len = read_byte(buf, head) as usize;
+        for i in 0..len {
+            out[i] = read_byte(buf, head.wrapping_add(1 + i));
+        }
+
+        self.queue.head.store(head.wrapping_add(1 + len), Ordering::Release);
+        Some(len)
+    }
+}
+
+/// Writes `byte` at ring index `idx`, wrapping around the buffer.
+fn write_byte(buf: &mut [u8; QUEUE_CAPACITY], idx: usize, byte: u8) {
+    buf[idx & (QUEUE_CAPACITY - 1)] = byte;
+}
+
+/// Reads the byte at ring index `idx`, wrapping around the buffer.
+fn read_byte(buf: &[u8; QUEUE_CAPACITY], idx: usize) -> u8 {
+    buf[idx & (QUEUE_CAPACITY - 1)]
+}
@@ -0,0 +1,89 @@
+//! Scan reports: advertisements observed while the link layer is scanning.
+//!
+//! [`encode`] packs an observed advertisement into a frame for the queue
+//! shared with `idle()`; [`ScanReport::parse`] unpacks it back out there, and
+//! [`ScanReport::ad_structures`] reuses [`AdStructureIter`] to walk its AD
+//! structures.
+
+use super::ad_structure::AdStructureIter;
+use super::{AddressKind, DeviceAddress, MAX_PDU_SIZE};
+
+/// Size of a report frame's fixed header: address kind (1) + RSSI (1) +
+/// address (6).
+const HEADER_SIZE: usize = 8;
+
+/// The largest AD payload a report frame can carry: a spec-maximal
+/// advertisement's payload, minus the AdvA it's already reported separately.
+pub const MAX_AD_DATA: usize = MAX_PDU_SIZE - 6;
+
+/// The largest report frame [`encode`] can produce.
+pub const MAX_REPORT_SIZE: usize = HEADER_SIZE + MAX_AD_DATA;
+
+/// Packs an observed advertisement into `out`, returning the frame length.
+///
+/// `ad_data` is truncated to [`MAX_AD_DATA`] octets if it doesn't fit, which
+/// a spec-compliant advertisement never exceeds.
+pub fn encode(
+    out: &mut [u8; MAX_REPORT_SIZE],
+    addr: DeviceAddress,
+    rssi: i8,
+    ad_data: &[u8],
+) -> usize {
+    out[0] = match addr.kind() {
+        AddressKind::Public => 0,
+        AddressKind::Random => 1,
+    };
+    out[1] = rssi as u8;
+    out[2..8].copy_from_slice(addr.bytes());
+
+    let n = ad_data.len().min(MAX_AD_DATA);
+    out[8..8 + n].copy_from_slice(&ad_data[..n]);
+    HEADER_SIZE + n
+}
+
+/// One advertisement observed while scanning, unpacked from a report frame.
+pub struct ScanReport<'a> {
+    addr: DeviceAddress,
+    rssi: i8,
+    ad_data: &'a [u8],
+}
+
+impl<'a> ScanReport<'a> {
+    /// Parses a frame built by [`encode`].
+    ///
+    /// Returns `None` if `frame` is too short to hold the fixed header.
+    pub fn parse(frame: &'a [u8]) -> Option<Self> {
+        if frame.len() < HEADER_SIZE {
+            return None;
+        }
+
+        let kind = if frame[0] == 0 {
+            AddressKind::Public
+        } else {
+            AddressKind::Random
+        };
+        let mut addr = [0; 6];
+        addr.copy_from_slice(&frame[2..8]);
+
+        Some(ScanReport {
+            addr: DeviceAddress::new(addr, kind),
+            rssi: frame[1] as i8,
+            ad_data: &frame[8..],
+        })
+    }
+
+    /// The advertiser's device address.
+    pub fn address(&self) -> DeviceAddress {
+        self.addr
+    }
+
+    /// The received signal strength, in dBm.
+    pub fn rssi(&self) -> i8 {
+        self.rssi
+    }
+
+    /// Iterates over the AD structures carried in the advertisement.
+    pub fn ad_structures(&self) -> AdStructureIter<'a> {
+        AdStructureIter::new(self.ad_data)
+    }
+}
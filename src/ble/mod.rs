@@ -0,0 +1,13 @@
+//! A minimal Bluetooth Low Energy stack.
+//!
+//! The stack is split into layers, mirroring the BLE specification:
+//!
+//! * [`link`] implements the Link Layer: advertising, the connection state
+//!   machine, and the framing of data-channel PDUs.
+//! * [`l2cap`] reassembles and dispatches the L2CAP frames carried over a
+//!   connection's data channels.
+//! * [`gatt`] implements a GATT server on top of L2CAP's fixed ATT channel.
+
+pub mod gatt;
+pub mod l2cap;
+pub mod link;
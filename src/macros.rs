@@ -0,0 +1,26 @@
+//! Utility macros used throughout the crate.
+
+/// Prints to the host's stderr via semihosting, without a trailing newline.
+///
+/// This is only active when the crate is built with debug assertions enabled;
+/// in release builds the (very slow) semihosting call is compiled out entirely
+/// so it cannot sneak into production firmware.
+macro_rules! heprint {
+    ( $($arg:tt)* ) => {{
+        #[cfg(debug_assertions)]
+        {
+            use ::cortex_m_semihosting::hio;
+            use ::core::fmt::Write;
+            if let Ok(mut out) = hio::hstderr() {
+                write!(out, $($arg)*).ok();
+            }
+        }
+    }};
+}
+
+/// Like [`heprint!`], but appends a newline.
+macro_rules! heprintln {
+    () => { heprint!("\n") };
+    ( $fmt:expr ) => { heprint!(concat!($fmt, "\n")) };
+    ( $fmt:expr, $($arg:tt)* ) => { heprint!(concat!($fmt, "\n"), $($arg)*) };
+}
@@ -1,4 +1,4 @@
-#![no_std]
+#![cfg_attr(not(test), no_std)]
 
 #[macro_use]
 extern crate nb;
@@ -19,29 +19,45 @@ pub mod ble;
 mod macros;
 mod temp;
 mod radio;
+mod responder;
 
+use ble::gatt::{Attribute, BatteryServiceAttrs, GattServer};
+use ble::link::queue::PacketQueue;
 use ble::link::{LinkLayer, AddressKind, DeviceAddress};
 use ble::link::ad_structure::{AdStructure, Flags};
 pub use ble::link::MAX_PDU_SIZE;
 
 use temp::Temp;
-use radio::{BleRadio, Baseband};
+use radio::{BleRadio, BleTimer, Baseband};
+use responder::Responder;
 
 use cortex_m::asm;
 use rtfm::{app, Threshold};
 use byteorder::{ByteOrder, LittleEndian};
 
 use core::time::Duration;
-use core::u32;
+
+/// PDUs received on a data channel, handed from the `RADIO` ISR to the
+/// [`Responder`] running in `idle()`.
+static RX_QUEUE: PacketQueue = PacketQueue::new();
+/// Replies built by the [`Responder`], sent out by the `RADIO` ISR on the
+/// next connection event.
+static TX_QUEUE: PacketQueue = PacketQueue::new();
+
+/// Current battery level, in percent, backing the Battery Service below.
+static BATTERY_LEVEL: [u8; 1] = [100];
+
+/// The device's GATT database: just a Battery Service for now.
+static ATTRIBUTES: [Attribute; 3] = BatteryServiceAttrs::attributes(&BATTERY_LEVEL);
 
 app! {
     device: nrf51,
 
     resources: {
-        static BLE_TX_BUF: ::radio::PacketBuffer = [0; ::MAX_PDU_SIZE + 1];
-        static BLE_RX_BUF: ::radio::PacketBuffer = [0; ::MAX_PDU_SIZE + 1];
+        static BLE_TX_BUF: ::radio::PacketBuffer = [0; ::MAX_PDU_SIZE + 2];
+        static BLE_RX_BUF: ::radio::PacketBuffer = [0; ::MAX_PDU_SIZE + 2];
         static BASEBAND: Baseband;
-        static BLE_TIMER: nrf51::TIMER0;
+        static RESPONDER: Responder<GattServer<'static>>;
     },
 
     init: {
@@ -49,18 +65,18 @@ app! {
     },
 
     idle: {
-        resources: [BASEBAND],
+        resources: [RESPONDER],
     },
 
     tasks: {
         RADIO: {
             path: radio,
-            resources: [BASEBAND, BLE_TIMER],
+            resources: [BASEBAND],
         },
 
         TIMER0: {
             path: radio_timer,
-            resources: [BASEBAND, BLE_TIMER],
+            resources: [BASEBAND],
         }
     },
 }
@@ -74,16 +90,6 @@ fn init(p: init::Peripherals, res: init::Resources) -> init::LateResources {
     p.device.CLOCK.tasks_hfclkstart.write(|w| unsafe { w.bits(1) });
     while p.device.CLOCK.events_hfclkstarted.read().bits() == 0 {}
 
-    // TIMER0 cfg, 32 bit @ 1 MHz
-    // Mostly copied from the `nrf51-hal` crate.
-    p.device.TIMER0.bitmode.write(|w| w.bitmode()._32bit());
-    p.device.TIMER0.prescaler.write(|w| unsafe { w.prescaler().bits(4) });
-    p.device.TIMER0.intenset.write(|w| w.compare0().set());
-    p.device.TIMER0.shorts.write(|w| w
-        .compare0_clear().enabled()
-        .compare0_stop().enabled()
-    );
-
     let mut devaddr = [0u8; 6];
     let devaddr_lo = p.device.FICR.deviceaddr[0].read().bits();
     let devaddr_hi = p.device.FICR.deviceaddr[1].read().bits() as u16;
@@ -103,8 +109,15 @@ fn init(p: init::Peripherals, res: init::Resources) -> init::LateResources {
         AdStructure::CompleteLocalName("CONCVRRENS CERTA CELERIS"),
     ]);
 
+    let radio = BleRadio::new(p.device.RADIO, &p.device.FICR, res.BLE_TX_BUF);
+    let timer = BleTimer::new(p.device.TIMER0);
+    let (rx_producer, rx_consumer) = RX_QUEUE.split();
+    let (tx_producer, tx_consumer) = TX_QUEUE.split();
+    let mut baseband = Baseband::new(radio, res.BLE_RX_BUF, timer, ll, rx_producer, tx_consumer);
+    let responder = Responder::new(rx_consumer, tx_producer, GattServer::new(&ATTRIBUTES));
+
     // Queue first baseband update
-    cfg_timer(&p.device.TIMER0, Some(Duration::from_millis(1)));
+    baseband.start();
 
     let mut temp = Temp::new(p.device.TEMP);
     temp.start_measurement();
@@ -112,47 +125,23 @@ fn init(p: init::Peripherals, res: init::Resources) -> init::LateResources {
     heprintln!("{}°C", temp);
 
     init::LateResources {
-        BASEBAND: Baseband::new(BleRadio::new(p.device.RADIO, &p.device.FICR, res.BLE_TX_BUF), res.BLE_RX_BUF, ll),
-        BLE_TIMER: p.device.TIMER0,
+        BASEBAND: baseband,
+        RESPONDER: responder,
     }
 }
 
-fn idle(_t: &mut Threshold, _res: idle::Resources) -> ! {
+fn idle(_t: &mut Threshold, mut res: idle::Resources) -> ! {
     loop {
+        res.RESPONDER.process();
         asm::wfi();
     }
 }
 
 fn radio(_t: &mut Threshold, mut res: RADIO::Resources) {
-    if let Some(new_timeout) = res.BASEBAND.interrupt() {
-        cfg_timer(&res.BLE_TIMER, Some(new_timeout));
-    }
+    res.BASEBAND.interrupt();
 }
 
 fn radio_timer(_t: &mut Threshold, mut res: TIMER0::Resources) {
     heprint!("T");
-    let maybe_next_update = res.BASEBAND.update();
-    cfg_timer(&res.BLE_TIMER, maybe_next_update);
-}
-
-/// Reconfigures TIMER0 to raise an interrupt after `duration` has elapsed.
-///
-/// TIMER0 is stopped if `duration` is `None`.
-///
-/// Note that if the timer has already queued an interrupt, the task will still be run after the
-/// timer is stopped by this function.
-fn cfg_timer(t: &nrf51::TIMER0, duration: Option<Duration>) {
-    // Timer activation code is also copied from the `nrf51-hal` crate.
-    if let Some(duration) = duration {
-        assert!(duration.as_secs() < ((u32::MAX - duration.subsec_micros()) / 1_000_000) as u64);
-        let us = (duration.as_secs() as u32) * 1_000_000 + duration.subsec_micros();
-        t.cc[0].write(|w| unsafe { w.bits(us) });
-        t.events_compare[0].reset();   // acknowledge last compare event (FIXME unnecessary?)
-        t.tasks_clear.write(|w| unsafe { w.bits(1) });
-        t.tasks_start.write(|w| unsafe { w.bits(1) });
-    } else {
-        t.tasks_stop.write(|w| unsafe { w.bits(1) });
-        t.tasks_clear.write(|w| unsafe { w.bits(1) });
-        t.events_compare[0].reset();   // acknowledge last compare event (FIXME unnecessary?)
-    }
+    res.BASEBAND.update();
 }
@@ -0,0 +1,481 @@
+//! nRF51 `RADIO`/`TIMER0` drivers and the baseband glue to the link layer.
+//!
+//! This module is the nrf51-specific implementation of the
+//! [`HardwareInterface`] abstraction. [`BleRadio`] implements [`Transmitter`]
+//! and also drives reception; [`BleTimer`] wraps `TIMER0` and implements the
+//! [`BleTimerTrait`]. [`Baseband`] owns both, plus the receive buffer and the
+//! [`LinkLayer`], and is driven from the `RADIO` and `TIMER0` interrupt
+//! handlers.
+
+use core::time::Duration;
+use core::u32;
+
+use nrf51::{FICR, RADIO, TIMER0};
+
+use ble::link::advertising::AdvertisingChannel;
+use ble::link::data::DataChannel;
+use ble::link::queue::{Consumer, Producer, MAX_FRAME_SIZE};
+use ble::link::{
+    BleTimer as BleTimerTrait, Cmd, HardwareInterface, LinkLayer, RadioCmd, Transmitter,
+    MAX_PDU_SIZE,
+};
+
+/// A packet buffer large enough for any PDU the stack handles, plus the two
+/// header octets (S0 + LENGTH) the RADIO stores in front of the payload.
+pub type PacketBuffer = [u8; MAX_PDU_SIZE + 2];
+
+/// The access address used on all advertising channels (fixed by the spec).
+const ADVERTISING_ADDRESS: u32 = 0x8E89_BED6;
+/// The CRC preset used on advertising channels.
+const ADVERTISING_CRC_PRESET: u32 = 0x0055_5555;
+
+/// Marker type implementing [`HardwareInterface`] for the nRF51.
+///
+/// This binds the generic [`LinkLayer`] to the concrete nrf51 radio and timer.
+pub enum Nrf51 {}
+
+impl HardwareInterface for Nrf51 {
+    type Timer = BleTimer;
+    type Tx = BleRadio;
+}
+
+/// Transmit power levels the nRF51 `RADIO.TXPOWER` register supports.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum TxPower {
+    /// +4 dBm.
+    Pos4dBm,
+    /// 0 dBm.
+    ZerodBm,
+    /// -4 dBm.
+    Neg4dBm,
+    /// -8 dBm.
+    Neg8dBm,
+    /// -12 dBm.
+    Neg12dBm,
+    /// -16 dBm.
+    Neg16dBm,
+    /// -20 dBm.
+    Neg20dBm,
+    /// -30 dBm.
+    Neg30dBm,
+}
+
+/// The PHY used on a connection's data channels.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Phy {
+    /// The LE 1M PHY. Used for advertising, and the only data-channel PHY
+    /// this hardware actually supports.
+    Le1M,
+    /// The LE 2M PHY.
+    ///
+    /// Not implemented: the nRF51 `RADIO` has no BLE 2M PHY mode (that's an
+    /// nRF52-and-later feature) or any other way to shorten the preamble;
+    /// [`set_data_phy`](BleRadio::set_data_phy) rejects it.
+    Le2M,
+}
+
+/// Driver for the nRF51 `RADIO`, configured for BLE on the 1 Mbit LE PHY.
+pub struct BleRadio {
+    radio: RADIO,
+    tx_buf: &'static mut PacketBuffer,
+    /// PHY to apply on the next data-channel rx/tx; always [`Phy::Le1M`]
+    /// today, since that's all this hardware can do.
+    phy: Phy,
+}
+
+impl BleRadio {
+    /// Initializes the `RADIO` for BLE operation.
+    ///
+    /// `tx_buf` is the statically allocated transmit buffer used as the radio's
+    /// DMA packet pointer.
+    pub fn new(radio: RADIO, ficr: &FICR, tx_buf: &'static mut PacketBuffer) -> Self {
+        assert!(ficr.overrideen.read().ble_1mbit().is_override());
+
+        // Apply the BLE 1 Mbit override values from the FICR, as required by the
+        // nRF51 reference manual.
+        radio.override0.write(|w| unsafe { w.override0().bits(ficr.ble_1mbit[0].read().bits()) });
+        radio.override1.write(|w| unsafe { w.override1().bits(ficr.ble_1mbit[1].read().bits()) });
+        radio.override2.write(|w| unsafe { w.override2().bits(ficr.ble_1mbit[2].read().bits()) });
+        radio.override3.write(|w| unsafe { w.override3().bits(ficr.ble_1mbit[3].read().bits()) });
+        radio.override4.write(|w| unsafe {
+            w.override4().bits(ficr.ble_1mbit[4].read().bits()).enable().set_bit()
+        });
+
+        radio.mode.write(|w| w.mode().ble_1mbit());
+
+        // LENGTH is an 8-bit field, S0 is one byte, S1 is unused.
+        radio.pcnf0.write(|w| unsafe { w.s0len().set_bit().lflen().bits(8).s1len().bits(0) });
+        radio.pcnf1.write(|w| unsafe {
+            w.maxlen()
+                .bits(MAX_PDU_SIZE as u8)
+                .balen()
+                .bits(3)
+                .whiteen()
+                .set_bit()
+        });
+
+        // CRC is 3 bytes with the BLE polynomial.
+        radio.crccnf.write(|w| w.len().three());
+        radio.crcpoly.write(|w| unsafe { w.crcpoly().bits(0x0000_065B) });
+
+        radio.tifs.write(|w| unsafe { w.tifs().bits(150) });
+
+        // Raise the RADIO NVIC interrupt on END (a transmission or reception
+        // just completed); without this the peripheral's own events never
+        // reach the CPU, no matter how `radio()` is bound in the `app!`
+        // macro.
+        radio.intenset.write(|w| w.end().set_bit());
+
+        BleRadio { radio, tx_buf, phy: Phy::Le1M }
+    }
+
+    /// Sets the transmit power used for all subsequent transmissions.
+    pub fn set_tx_power(&mut self, power: TxPower) {
+        self.radio.txpower.write(|w| match power {
+            TxPower::Pos4dBm => w.txpower().pos4d_bm(),
+            TxPower::ZerodBm => w.txpower()._0d_bm(),
+            TxPower::Neg4dBm => w.txpower().neg4d_bm(),
+            TxPower::Neg8dBm => w.txpower().neg8d_bm(),
+            TxPower::Neg12dBm => w.txpower().neg12d_bm(),
+            TxPower::Neg16dBm => w.txpower().neg16d_bm(),
+            TxPower::Neg20dBm => w.txpower().neg20d_bm(),
+            TxPower::Neg30dBm => w.txpower().neg30d_bm(),
+        });
+    }
+
+    /// Selects the PHY used on data channels from the next connection event
+    /// onward. Advertising always stays on the LE 1M PHY, as the spec
+    /// requires.
+    ///
+    /// Returns `false` without changing anything if `phy` isn't supported by
+    /// this hardware; only [`Phy::Le1M`] is.
+    pub fn set_data_phy(&mut self, phy: Phy) -> bool {
+        if phy != Phy::Le1M {
+            return false;
+        }
+        self.phy = phy;
+        true
+    }
+
+    /// Applies `self.phy` to the `MODE` register ahead of a data-channel
+    /// rx/tx.
+    fn apply_phy(&mut self) {
+        match self.phy {
+            Phy::Le1M => self.radio.mode.write(|w| w.mode().ble_1mbit()),
+            Phy::Le2M => unreachable!("set_data_phy rejects Le2M"),
+        }
+    }
+
+    /// Selects the transmit/receive frequency from a MHz offset above 2400 MHz.
+    fn set_frequency(&mut self, offset: u8) {
+        self.radio.frequency.write(|w| unsafe { w.frequency().bits(offset) });
+    }
+
+    /// Sets the logical address, whitening init value and CRC init value.
+    fn set_access(&mut self, access_address: u32, crc_init: u32, whiteiv: u8) {
+        // Use logical address 0, with the high byte as the prefix.
+        self.radio.base0.write(|w| unsafe { w.bits(access_address << 8) });
+        self.radio
+            .prefix0
+            .write(|w| unsafe { w.ap0().bits((access_address >> 24) as u8) });
+        self.radio.txaddress.write(|w| unsafe { w.txaddress().bits(0) });
+        self.radio.rxaddresses.write(|w| w.addr0().enabled());
+        self.radio.crcinit.write(|w| unsafe { w.crcinit().bits(crc_init) });
+        self.radio.datawhiteiv.write(|w| unsafe { w.datawhiteiv().bits(whiteiv) });
+    }
+
+    /// Starts a transmission of the current `tx_buf` contents.
+    fn start_tx(&mut self) {
+        self.radio
+            .packetptr
+            .write(|w| unsafe { w.bits(self.tx_buf.as_ptr() as u32) });
+        self.radio.events_end.reset();
+        self.radio.tasks_txen.write(|w| unsafe { w.bits(1) });
+    }
+
+    /// Arms reception on the currently configured channel, and starts RSSI
+    /// sampling so [`rssi`](BleRadio::rssi) is valid once the packet arrives.
+    fn start_rx(&mut self) {
+        self.radio.events_end.reset();
+        self.radio.tasks_rxen.write(|w| unsafe { w.bits(1) });
+        self.radio.tasks_rssistart.write(|w| unsafe { w.bits(1) });
+    }
+
+    /// Returns the RSSI of the last received packet, in dBm.
+    fn rssi(&self) -> i8 {
+        -(self.radio.rssisample.read().rssisample().bits() as i8)
+    }
+
+    /// Listens on an advertising `channel` for a scan or connection request,
+    /// or, while scanning, for other devices' advertisements.
+    fn receive_advertising(&mut self, channel: AdvertisingChannel) {
+        // Advertising always uses the LE 1M PHY, regardless of what PHY a
+        // previous connection's data channels were using.
+        self.radio.mode.write(|w| w.mode().ble_1mbit());
+        self.set_access(ADVERTISING_ADDRESS, ADVERTISING_CRC_PRESET, channel.channel());
+        self.set_frequency(channel.freq_offset());
+        self.start_rx();
+    }
+
+    /// Arms reception on a data `channel` for the next connection event.
+    fn receive_data(&mut self, channel: DataChannel, access_address: u32, crc_init: u32) {
+        self.apply_phy();
+        self.set_access(access_address, crc_init, channel.index());
+        self.set_frequency(channel.freq_offset());
+        self.start_rx();
+    }
+
+    /// Stops the radio.
+    fn off(&mut self) {
+        self.radio.tasks_disable.write(|w| unsafe { w.bits(1) });
+    }
+
+    /// Points the radio's DMA at `rx_buf` for the next reception.
+    fn set_rx_buffer(&mut self, rx_buf: &mut PacketBuffer) {
+        self.radio
+            .packetptr
+            .write(|w| unsafe { w.bits(rx_buf.as_ptr() as u32) });
+    }
+
+    /// Returns `true` if the CRC of the last received packet was valid.
+    fn crc_ok(&self) -> bool {
+        self.radio.crcstatus.read().crcstatus().is_crcok()
+    }
+
+    /// Fills `tx_buf` with `header`, the length octet and `payload`.
+    fn fill_tx(&mut self, header: u8, payload: &[u8]) {
+        self.tx_buf[0] = header;
+        self.tx_buf[1] = payload.len() as u8;
+        self.tx_buf[2..2 + payload.len()].copy_from_slice(payload);
+    }
+}
+
+impl Transmitter for BleRadio {
+    fn transmit_advertising(&mut self, header: u8, payload: &[u8], channel: AdvertisingChannel) {
+        // Advertising always uses the LE 1M PHY, regardless of what PHY a
+        // previous connection's data channels were using.
+        self.radio.mode.write(|w| w.mode().ble_1mbit());
+        self.set_access(ADVERTISING_ADDRESS, ADVERTISING_CRC_PRESET, channel.channel());
+        self.set_frequency(channel.freq_offset());
+        self.fill_tx(header, payload);
+        self.start_tx();
+    }
+
+    fn transmit_data(
+        &mut self,
+        access_address: u32,
+        crc_init: u32,
+        header: u8,
+        payload: &[u8],
+        channel: DataChannel,
+    ) {
+        self.apply_phy();
+        self.set_access(access_address, crc_init, channel.index());
+        self.set_frequency(channel.freq_offset());
+        self.fill_tx(header, payload);
+        self.start_tx();
+    }
+}
+
+/// Wraps `TIMER0` as the link layer's scheduling timer.
+pub struct BleTimer {
+    timer: TIMER0,
+}
+
+impl BleTimer {
+    /// Configures `TIMER0` as a 32-bit, 1 MHz timer raising a compare-0
+    /// interrupt, and takes ownership of it.
+    pub fn new(timer: TIMER0) -> Self {
+        // TIMER0 cfg, 32 bit @ 1 MHz. Mostly copied from the `nrf51-hal` crate.
+        timer.bitmode.write(|w| w.bitmode()._32bit());
+        timer.prescaler.write(|w| unsafe { w.prescaler().bits(4) });
+        timer.intenset.write(|w| w.compare0().set());
+        timer.shorts.write(|w| w.compare0_clear().enabled().compare0_stop().enabled());
+
+        BleTimer { timer }
+    }
+}
+
+impl BleTimerTrait for BleTimer {
+    /// Reconfigures `TIMER0` to raise an interrupt after `duration`, or stops it
+    /// when `duration` is `None`.
+    ///
+    /// Note that if the timer has already queued an interrupt, the task will
+    /// still run after the timer is stopped here.
+    fn configure_interrupt(&mut self, duration: Option<Duration>) {
+        // Timer activation code is also copied from the `nrf51-hal` crate.
+        if let Some(duration) = duration {
+            assert!(duration.as_secs() < ((u32::MAX - duration.subsec_micros()) / 1_000_000) as u64);
+            let us = (duration.as_secs() as u32) * 1_000_000 + duration.subsec_micros();
+            self.timer.cc[0].write(|w| unsafe { w.bits(us) });
+            self.timer.events_compare[0].reset();
+            self.timer.tasks_clear.write(|w| unsafe { w.bits(1) });
+            self.timer.tasks_start.write(|w| unsafe { w.bits(1) });
+        } else {
+            self.timer.tasks_stop.write(|w| unsafe { w.bits(1) });
+            self.timer.tasks_clear.write(|w| unsafe { w.bits(1) });
+            self.timer.events_compare[0].reset();
+        }
+    }
+}
+
+/// The connection parameters the radio is currently listening with, used to
+/// address a queued reply to the right access address/channel.
+#[derive(Copy, Clone)]
+struct DataChannelContext {
+    channel: DataChannel,
+    access_address: u32,
+    crc_init: u32,
+}
+
+/// Owns the radio, the timer and the link layer, and shuttles data between
+/// them. This is the nrf51 implementation of the baseband.
+///
+/// Received data-channel PDUs are pushed into `rx_queue` instead of being
+/// parsed here, and any reply already queued in `tx_queue` by the
+/// [`Responder`] is sent out in the same connection event. This keeps the
+/// time spent in the `RADIO`/`TIMER0` interrupts bounded regardless of what
+/// the upper layers do with a PDU.
+///
+/// [`Responder`]: ::responder::Responder
+pub struct Baseband {
+    radio: BleRadio,
+    timer: BleTimer,
+    rx_buf: &'static mut PacketBuffer,
+    ll: LinkLayer<Nrf51>,
+    rx_queue: Producer<'static>,
+    tx_queue: Consumer<'static>,
+    /// Set while listening on a data channel, so a received packet's
+    /// connection context is known without re-deriving it from `cmd`.
+    data_ctx: Option<DataChannelContext>,
+}
+
+impl Baseband {
+    /// Creates the baseband from an initialized radio, a receive buffer, a
+    /// timer, a link layer that has already been told what to do (e.g. to
+    /// advertise), and the two ends of the packet queue shared with the
+    /// [`Responder`]: `rx_queue` for PDUs received on data channels, `tx_queue`
+    /// for replies to send on the next one.
+    ///
+    /// [`Responder`]: ::responder::Responder
+    pub fn new(
+        radio: BleRadio,
+        rx_buf: &'static mut PacketBuffer,
+        timer: BleTimer,
+        ll: LinkLayer<Nrf51>,
+        rx_queue: Producer<'static>,
+        tx_queue: Consumer<'static>,
+    ) -> Self {
+        Baseband {
+            radio,
+            timer,
+            rx_buf,
+            ll,
+            rx_queue,
+            tx_queue,
+            data_ctx: None,
+        }
+    }
+
+    /// Arms the timer for the first link-layer update, kicking off advertising.
+    pub fn start(&mut self) {
+        self.timer.configure_interrupt(Some(Duration::from_millis(1)));
+    }
+
+    /// Sets the transmit power used for all subsequent transmissions.
+    pub fn set_tx_power(&mut self, power: TxPower) {
+        self.radio.set_tx_power(power);
+    }
+
+    /// Selects the PHY used on data channels from the next connection event
+    /// onward; see [`BleRadio::set_data_phy`].
+    pub fn set_data_phy(&mut self, phy: Phy) -> bool {
+        self.radio.set_data_phy(phy)
+    }
+
+    /// Handles a `RADIO` interrupt: a packet has just been received.
+    pub fn interrupt(&mut self) {
+        // Only received packets with a valid CRC are handed up. Re-arm
+        // reception on the same channel rather than leaving the radio
+        // disabled until the next scheduled `update` (a full advertising or
+        // connection interval away).
+        if !self.radio.crc_ok() {
+            self.radio.set_rx_buffer(self.rx_buf);
+            self.radio.start_rx();
+            return;
+        }
+
+        let header = self.rx_buf[0];
+        let len = self.rx_buf[1] as usize;
+        let payload_end = 2 + len.min(MAX_PDU_SIZE);
+        let payload = &self.rx_buf[2..payload_end];
+
+        // Hand data-channel PDUs to the `Responder` instead of parsing them
+        // here, and send back whatever reply it already queued up.
+        let mut replied = false;
+        if let Some(ctx) = self.data_ctx {
+            let mut frame = [0; MAX_FRAME_SIZE];
+            frame[0] = header;
+            frame[1..1 + payload.len()].copy_from_slice(payload);
+            self.rx_queue.enqueue(&frame[..1 + payload.len()]);
+
+            let mut reply = [0; MAX_FRAME_SIZE];
+            if let Some(len) = self.tx_queue.dequeue(&mut reply) {
+                self.radio.transmit_data(
+                    ctx.access_address,
+                    ctx.crc_init,
+                    reply[0],
+                    &reply[1..len],
+                    ctx.channel,
+                );
+                replied = true;
+            }
+        }
+
+        let rssi = self.radio.rssi();
+        let cmd = self.ll.process_packet(header, payload, rssi, &mut self.radio);
+        // Don't let a stale radio command cut off the reply we just started
+        // transmitting; the next scheduled `update` will re-arm reception.
+        if !replied {
+            self.apply_radio(&cmd);
+        }
+        // A received packet only re-arms the timer when the link layer asks for
+        // it; `None` leaves the already-scheduled next event in place.
+        if let Some(duration) = cmd.next_update {
+            self.timer.configure_interrupt(Some(duration));
+        }
+    }
+
+    /// Handles a `TIMER0` interrupt: the link layer's next scheduled action is
+    /// due.
+    pub fn update(&mut self) {
+        let cmd = self.ll.update(&mut self.radio);
+        self.apply_radio(&cmd);
+        self.timer.configure_interrupt(cmd.next_update);
+    }
+
+    /// Applies the reception part of a link-layer [`Cmd`] to the radio.
+    fn apply_radio(&mut self, cmd: &Cmd) {
+        self.data_ctx = None;
+        match cmd.radio {
+            RadioCmd::Off => self.radio.off(),
+            RadioCmd::ListenAdvertising { channel } => {
+                self.radio.set_rx_buffer(self.rx_buf);
+                self.radio.receive_advertising(channel);
+            }
+            RadioCmd::ListenData {
+                channel,
+                access_address,
+                crc_init,
+            } => {
+                self.radio.set_rx_buffer(self.rx_buf);
+                self.radio.receive_data(channel, access_address, crc_init);
+                self.data_ctx = Some(DataChannelContext {
+                    channel,
+                    access_address,
+                    crc_init,
+                });
+            }
+        }
+    }
+}
@@ -0,0 +1,84 @@
+//! Upper-layer packet processing, run from `idle()` instead of interrupt
+//! context.
+//!
+//! [`Responder`] drains the RX half of a [`PacketQueue`] filled by the
+//! [`Baseband`] ISR, reassembles the L2CAP frames carried in data-channel
+//! PDUs, dispatches completed ones to a [`ChannelMapper`], and fragments any
+//! reply back into the TX half for the `Baseband` to send at the next
+//! connection event. Running here, rather than in the `RADIO`/`TIMER0`
+//! handlers, keeps interrupt time bounded regardless of how much work the
+//! upper layers end up doing.
+//!
+//! [`Baseband`]: ::radio::Baseband
+//! [`PacketQueue`]: ::ble::link::queue::PacketQueue
+
+use ble::l2cap::{ChannelMapper, Fragmenter, Reassembler, MAX_SDU_SIZE};
+use ble::link::data::Llid;
+use ble::link::queue::{Consumer, Producer, MAX_FRAME_SIZE};
+use ble::link::MAX_PDU_SIZE;
+
+/// Drains received data-channel PDUs, reassembles and dispatches L2CAP
+/// frames, and queues any replies for transmission.
+pub struct Responder<M> {
+    rx: Consumer<'static>,
+    tx: Producer<'static>,
+    reassembler: Reassembler,
+    mapper: M,
+}
+
+impl<M: ChannelMapper> Responder<M> {
+    /// Creates a responder from the consuming half of the RX queue, the
+    /// producing half of the TX queue, and the [`ChannelMapper`] that answers
+    /// received L2CAP frames.
+    pub fn new(rx: Consumer<'static>, tx: Producer<'static>, mapper: M) -> Self {
+        Responder {
+            rx,
+            tx,
+            reassembler: Reassembler::new(),
+            mapper,
+        }
+    }
+
+    /// Processes every LL data PDU currently sitting in the RX queue.
+    ///
+    /// Call this from `idle()`; it returns once the queue is empty, leaving
+    /// the caller free to go back to sleep until the next interrupt wakes it.
+    pub fn process(&mut self) {
+        let mut frame = [0; MAX_FRAME_SIZE];
+        while let Some(len) = self.rx.dequeue(&mut frame) {
+            self.process_pdu(&frame[..len]);
+        }
+    }
+
+    /// Handles a single received LL data PDU: `pdu[0]` is the header octet,
+    /// the rest is the payload.
+    fn process_pdu(&mut self, pdu: &[u8]) {
+        let llid = Llid::from_header(pdu[0]);
+        let payload = &pdu[1..];
+
+        if let Some(cid) = self.reassembler.feed(llid, payload) {
+            // Copy the SDU out before calling into the mapper, which may need
+            // to write its reply into a buffer of its own.
+            let mut sdu = [0; MAX_SDU_SIZE];
+            let sdu_len = self.reassembler.sdu().len();
+            sdu[..sdu_len].copy_from_slice(self.reassembler.sdu());
+
+            let mut response = [0; MAX_SDU_SIZE];
+            if let Some(len) = self.mapper.handle(cid, &sdu[..sdu_len], &mut response) {
+                self.send(cid, &response[..len]);
+            }
+        }
+    }
+
+    /// Fragments `sdu` and queues it for transmission on `cid`.
+    fn send(&mut self, cid: u16, sdu: &[u8]) {
+        let mut fragmenter = Fragmenter::new(cid, sdu);
+        let mut pdu = [0; MAX_PDU_SIZE];
+        while let Some((llid, len)) = fragmenter.next(&mut pdu) {
+            let mut frame = [0; MAX_FRAME_SIZE];
+            frame[0] = llid.to_header();
+            frame[1..1 + len].copy_from_slice(&pdu[..len]);
+            self.tx.enqueue(&frame[..1 + len]);
+        }
+    }
+}
@@ -0,0 +1,48 @@
+//! Driver for the nRF51 on-chip temperature sensor (`TEMP` peripheral).
+
+use nrf51::TEMP;
+use fpa::I30F2;
+
+/// Handle to the `TEMP` peripheral, providing a single temperature measurement.
+pub struct Temp {
+    temp: TEMP,
+}
+
+impl Temp {
+    /// Creates a new temperature sensor driver, taking ownership of the `TEMP`
+    /// peripheral.
+    pub fn new(temp: TEMP) -> Self {
+        Temp { temp }
+    }
+
+    /// Starts a single temperature measurement.
+    ///
+    /// The result can be retrieved with [`read`], which returns `WouldBlock`
+    /// until the measurement is done.
+    ///
+    /// [`read`]: #method.read
+    pub fn start_measurement(&mut self) {
+        self.temp.events_datardy.reset();
+        self.temp.tasks_start.write(|w| unsafe { w.bits(1) });
+    }
+
+    /// Reads back the result of the last measurement started with
+    /// [`start_measurement`].
+    ///
+    /// Returns the temperature in degrees Celsius with a resolution of 0.25 °C.
+    /// This is an `nb` operation and returns `WouldBlock` until the measurement
+    /// has finished.
+    ///
+    /// [`start_measurement`]: #method.start_measurement
+    pub fn read(&mut self) -> ::nb::Result<I30F2, !> {
+        if self.temp.events_datardy.read().bits() == 0 {
+            return Err(::nb::Error::WouldBlock);
+        }
+
+        self.temp.events_datardy.reset();
+        // The raw value is in units of 0.25 °C, which maps directly onto the
+        // 2-bit fractional part of an `I30F2`.
+        let raw = self.temp.temp.read().bits() as i32;
+        Ok(I30F2::from_bits(raw))
+    }
+}